@@ -1,14 +1,16 @@
 use std::sync::mpsc::Sender;
 use std::io::Write;
-use std::collections::HashMap;
-use std::sync::RwLock;
 use std::mem::size_of_val;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
 
 use log::{debug, info, warn, error};
 
 use super::config::SharedConfig;
+use super::node_state::SharedState;
+use super::event_bus::{Event, SharedBus};
 use super::PublicKey;
-use super::{NODE_VERSION, UUID_TESTNET};
+use super::NODE_VERSION;
 use super::network::packet::{Flags, Packet};
 
 extern crate bincode;
@@ -18,6 +20,12 @@ use base58::ToBase58; // [u8].to_base58()
 
 type Command = super::network::packet::NghbrCmd;
 
+mod neighbours;
+use neighbours::NeighboursTable;
+pub use neighbours::NeighboursView;
+
+const NEIGHBOURS_DUMP_FILE: &str = "neighbours.json";
+
 #[derive(Default)]
 struct PeerInfo {
     /// build numbder
@@ -29,25 +37,31 @@ struct PeerInfo {
     /// the last repported consensus round
     round: u64,
     /// requires to be persistent
-    persistent: bool
+    persistent: bool,
+    /// time point the peer is added
+    connected_since: Option<SystemTime>,
+    /// time point the last ping is sent
+    ping_sent: Option<Instant>,
+    /// the last measured round trip time
+    rtt: Option<Duration>
 }
 
 pub struct Collaboration {
     tx_send: Sender<Packet>,
-    sequence: u64,
-    round: u64,
-    neighbours: RwLock<HashMap<PublicKey, PeerInfo>>,
+    state: SharedState,
+    bus: SharedBus,
+    neighbours: NeighboursTable,
     config: SharedConfig
 }
 
 impl Collaboration {
 
-    pub fn new(conf: SharedConfig, tx_send: Sender<Packet>) -> Collaboration {
+    pub fn new(conf: SharedConfig, state: SharedState, bus: SharedBus, view: NeighboursView, tx_send: Sender<Packet>) -> Collaboration {
         Collaboration {
             tx_send: tx_send,
-            sequence: 0,
-            round: 0,
-            neighbours: RwLock::new(HashMap::<PublicKey, PeerInfo>::new()),
+            state: state,
+            bus: bus,
+            neighbours: view.table.clone(),
             config: conf
         }
     }
@@ -66,8 +80,8 @@ impl Collaboration {
 
     pub fn ping_all(&self) {
         // send ping packet to all neigbours
-        let all = self.neighbours.read().unwrap();
-        for item in all.keys() {
+        let mut all = self.neighbours.write().unwrap();
+        for (item, info) in all.iter_mut() {
             let mut output: Vec<u8> = Vec::<u8>::new();
             match pack_ping(&mut output) {
                 Err(_) => {
@@ -84,6 +98,7 @@ impl Collaboration {
                                     warn!("failed send ping packet to {}: {}", item.to_base58(), e);
                                 },
                                 Ok(_) => {
+                                    info.ping_sent = Some(Instant::now());
                                     debug!("transfer ping packet to {}", item.to_base58());
                                 }
                             }
@@ -95,14 +110,62 @@ impl Collaboration {
 
     }
 
+    pub fn dump_neighbours(&self) {
+        let mut file_name;
+        {
+            let conf_guard = self.config.read().unwrap();
+            file_name = PathBuf::from(&conf_guard.data_dir);
+        }
+        file_name.push(NEIGHBOURS_DUMP_FILE);
+        let view = NeighboursView {
+            table: self.neighbours.clone(),
+            state: self.state.clone()
+        };
+        match view.dump(&file_name) {
+            Err(e) => {
+                warn!("failed to dump neighbours to {}: {}", file_name.display(), e);
+            }
+            Ok(_) => {
+                debug!("neighbours are dumped to {}", file_name.display());
+            }
+        }
+    }
+
+    fn sequence_and_round(&self) -> (u64, u64) {
+        let guard = self.state.read().unwrap();
+        (guard.sequence, guard.round)
+    }
+
+    /// stores neighbours' max sequence and round into the shared state
+    fn update_network_state(&self) {
+        let mut sequence = 0;
+        let mut round = 0;
+        {
+            let guard = self.neighbours.read().unwrap();
+            for info in guard.values() {
+                sequence = std::cmp::max(sequence, info.sequence);
+                round = std::cmp::max(round, info.round);
+            }
+        }
+        let mut guard = self.state.write().unwrap();
+        guard.network_sequence = sequence;
+        guard.network_round = round;
+    }
+
     fn handle_error(&self, _sender: &PublicKey, _bytes: Option<&[u8]>) {
 
     }
 
     fn handle_version_request(&self, sender: &PublicKey, _bytes: Option<&[u8]>) {
         // send version reply:
+        let uuid;
+        {
+            let conf_guard = self.config.read().unwrap();
+            uuid = conf_guard.uuid();
+        }
+        let (sequence, round) = self.sequence_and_round();
         let mut output: Vec<u8> = Vec::<u8>::new();
-        match pack_version_reply(&mut output, self.sequence, self.round) {
+        match pack_version_reply(&mut output, uuid, sequence, round) {
             Err(_) => {
                 error!("failed to serialize version reply");
             },
@@ -153,8 +216,12 @@ impl Collaboration {
                     debug!("new peer info rejected");
                 }
                 else {
-                    let guard = self.neighbours.read().unwrap();
-                    info!("add new neighbour, now total {}", guard.len());
+                    {
+                        let guard = self.neighbours.read().unwrap();
+                        info!("add new neighbour, now total {}", guard.len());
+                    }
+                    self.update_network_state();
+                    self.bus.publish(Event::PeerAdded(*sender));
                 }
             }
         };
@@ -162,8 +229,9 @@ impl Collaboration {
 
     fn handle_ping(&self, sender: &PublicKey, _bytes: Option<&[u8]>) {
         // send pong:
+        let (sequence, round) = self.sequence_and_round();
         let mut output: Vec<u8> = Vec::<u8>::new();
-        match pack_pong(&mut output, self.sequence, self.round) {
+        match pack_pong(&mut output, sequence, round) {
             Err(_) => {
                 error!("failed to serialize pong");
             },
@@ -208,6 +276,7 @@ impl Collaboration {
                     debug!("{} is not updated", sender.to_base58());
                 }
                 else {
+                    self.update_network_state();
                     let s: String;
                     if data.1 >= data.0 {
                         s = format!("+{}", &data.1 - &data.0);
@@ -263,6 +332,8 @@ impl Collaboration {
         match lost_peer {
             None => (),
             Some(item) => {
+                self.update_network_state();
+                self.bus.publish(Event::PeerLost(item.0));
                 if item.1.persistent {
                     // send version request
                     self.handle_node_found(&item.0);
@@ -281,6 +352,9 @@ impl Collaboration {
 
         let mut guard = self.neighbours.write().unwrap();
         let info = guard.get_mut(key).unwrap();
+        if let Some(sent) = info.ping_sent.take() {
+            info.rtt = Some(sent.elapsed());
+        }
         let mut updated = false;
         if info.sequence < data.0 {
             info.sequence = data.0;
@@ -293,7 +367,7 @@ impl Collaboration {
         updated
     }
 
-    fn try_add_peer(&mut self, key: &PublicKey, peer_info: PeerInfo) -> bool {
+    fn try_add_peer(&mut self, key: &PublicKey, mut peer_info: PeerInfo) -> bool {
 
         let exists: bool;
         let count: usize;
@@ -306,23 +380,23 @@ impl Collaboration {
         if exists {
             // read config
             let max_neighbours;
-            let mut min_version = NODE_VERSION;
+            let min_version;
+            let uuid;
             {
                 let conf_guard = self.config.read().unwrap();
                 max_neighbours = conf_guard.max_neighbours;
-                let tmp = conf_guard.min_compatible_version;
-                if tmp > 0 {
-                    min_version = tmp as u16;
-                }
+                min_version = conf_guard.min_version();
+                uuid = conf_guard.uuid();
             }
 
             // test compatibility
             if peer_info.version < min_version {
-                debug!("peer version is incompatible: {} < {}, reject", peer_info.version, NODE_VERSION);
+                debug!("peer version is incompatible: {} < {}, reject", peer_info.version, min_version);
                 return false;
             }
-            if peer_info.uuid != UUID_TESTNET {
-                debug!("peer blockchain is incompatible, reject");
+            // unknown blockchain is not compatible with any one
+            if uuid == 0 || peer_info.uuid != uuid {
+                debug!("peer blockchain is incompatible: {} != {}, reject", peer_info.uuid, uuid);
                 return false;
             }
 
@@ -334,6 +408,7 @@ impl Collaboration {
             }
         }
 
+        peer_info.connected_since = Some(SystemTime::now());
         let mut guard = self.neighbours.write().unwrap();
         match guard.insert(*key, peer_info) {
             None => (),
@@ -391,14 +466,14 @@ fn pack_version_request(output: &mut Vec<u8>) -> bincode::Result<()> {
     Ok(())
 }
 
-fn pack_version_reply(output: &mut Vec<u8>, sequence: u64, round: u64) -> bincode::Result<()> {
+fn pack_version_reply(output: &mut Vec<u8>, uuid: u64, sequence: u64, round: u64) -> bincode::Result<()> {
     let cmd_len = 1 + 1 + 2 + 8 + 8 + 8; // flags + cmd + version + uuid + sequence + round
     output.reserve(cmd_len);
 
     serialize_into(output.by_ref(), &Flags::N.bits())?;
     serialize_into(output.by_ref(), &(Command::VersionReply as u8))?;
     serialize_into(output.by_ref(), &NODE_VERSION)?;
-    serialize_into(output.by_ref(), &UUID_TESTNET)?;
+    serialize_into(output.by_ref(), &uuid)?;
     serialize_into(output.by_ref(), &sequence)?;
     serialize_into(output.by_ref(), &round)?;
 
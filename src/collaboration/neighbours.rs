@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, UNIX_EPOCH};
+use std::fs;
+use std::path::Path;
+use std::io;
+
+use super::super::PublicKey;
+use super::super::node_state::SharedState;
+use super::PeerInfo;
+
+extern crate base58;
+use base58::ToBase58; // [u8].to_base58()
+
+pub(super) type NeighboursTable = Arc<RwLock<HashMap<PublicKey, PeerInfo>>>;
+
+/// Neighbour info as it is seen at the moment of snapshot
+pub struct NeighbourSnapshot {
+    /// base58 encoded public key
+    pub id: String,
+    pub version: u16,
+    pub uuid: u64,
+    pub sequence: u64,
+    pub round: u64,
+    /// neighbour sequence minus our one: positive if neighbour is ahead of us
+    pub lag: i64,
+    /// last measured ping-pong round trip time
+    pub rtt_ms: Option<u64>,
+    pub persistent: bool,
+    /// unix time in seconds
+    pub connected_since: u64
+}
+
+/// Read-only access to the neighbours table
+#[derive(Clone)]
+pub struct NeighboursView {
+    pub(super) table: NeighboursTable,
+    pub(super) state: SharedState
+}
+
+impl NeighboursView {
+
+    pub fn new(state: SharedState) -> NeighboursView {
+        NeighboursView {
+            table: Arc::new(RwLock::new(HashMap::<PublicKey, PeerInfo>::new())),
+            state: state
+        }
+    }
+
+    pub fn snapshot(&self) -> Vec<NeighbourSnapshot> {
+        let our_sequence = self.state.read().unwrap().sequence;
+        let guard = self.table.read().unwrap();
+        let mut items: Vec<NeighbourSnapshot> = guard.iter()
+            .map(|(key, info)| NeighbourSnapshot {
+                id: key.to_base58(),
+                version: info.version,
+                uuid: info.uuid,
+                sequence: info.sequence,
+                round: info.round,
+                lag: info.sequence as i64 - our_sequence as i64,
+                rtt_ms: info.rtt.map(|d| d.as_millis() as u64),
+                persistent: info.persistent,
+                connected_since: match info.connected_since {
+                    None => 0,
+                    Some(t) => t.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+                }
+            })
+            .collect();
+        items.sort_by(|a, b| a.id.cmp(&b.id));
+        items
+    }
+
+    /// neighbours having block from and later: the most advanced and then the fastest first
+    pub fn best_for_sync(&self, from: u64) -> Vec<(PublicKey, u64)> {
+        let guard = self.table.read().unwrap();
+        let mut items: Vec<(&PublicKey, &PeerInfo)> = guard.iter()
+            .filter(|(_, info)| info.sequence >= from)
+            .collect();
+        items.sort_by(|a, b| b.1.sequence.cmp(&a.1.sequence)
+            .then(a.1.rtt.unwrap_or(Duration::from_secs(u64::MAX)).cmp(&b.1.rtt.unwrap_or(Duration::from_secs(u64::MAX)))));
+        items.into_iter().map(|(key, info)| (*key, info.sequence)).collect()
+    }
+
+    /// writes snapshot as JSON into file_name, the file is replaced atomically
+    pub fn dump(&self, file_name: &Path) -> io::Result<()> {
+        let json = to_json(&self.snapshot());
+        if let Some(dir) = file_name.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let tmp = file_name.with_extension("tmp");
+        fs::write(&tmp, json)?;
+        fs::rename(&tmp, file_name)
+    }
+}
+
+pub fn to_json(items: &[NeighbourSnapshot]) -> String {
+    let mut output = String::from("[");
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            output.push(',');
+        }
+        let rtt = match item.rtt_ms {
+            None => "null".to_string(),
+            Some(v) => v.to_string()
+        };
+        output.push_str(&format!(
+            "\n  {{\"id\":\"{}\",\"version\":{},\"uuid\":{},\"sequence\":{},\"round\":{},\"lag\":{},\"rtt_ms\":{},\"persistent\":{},\"connected_since\":{}}}",
+            item.id, item.version, item.uuid, item.sequence, item.round, item.lag, rtt, item.persistent, item.connected_since));
+    }
+    output.push_str("\n]\n");
+    output
+}
+
+#[test]
+fn test_to_json() {
+    let items = vec![
+        NeighbourSnapshot {
+            id: "HBxj19cnpayn46GSqBGyKQXMaLThH4quuPt5gf8aFndg".to_string(),
+            version: 502,
+            uuid: 1,
+            sequence: 100,
+            round: 200,
+            lag: -2,
+            rtt_ms: None,
+            persistent: true,
+            connected_since: 1_600_000_000
+        }
+    ];
+    assert_eq!(to_json(&items), "[\n  {\"id\":\"HBxj19cnpayn46GSqBGyKQXMaLThH4quuPt5gf8aFndg\",\"version\":502,\"uuid\":1,\"sequence\":100,\"round\":200,\"lag\":-2,\"rtt_ms\":null,\"persistent\":true,\"connected_since\":1600000000}\n]\n");
+    assert_eq!(to_json(&[]), "[\n]\n");
+}
@@ -20,6 +20,11 @@ impl Data {
 		}
 	}
 
+	/// count of rounds to keep transaction packets no longer referenced
+	pub fn packet_ttl(&self) -> u64 {
+		self.packet_ttl as u64
+	}
+
 	pub fn update(&mut self, prop: &HashMap<String, String>) -> bool {
 		let mut updated = false;
 		for (k, v) in prop.iter() {
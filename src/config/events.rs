@@ -59,6 +59,18 @@ impl Data {
 		}
 	}
 
+    pub fn report_big_bang(&self) -> bool {
+        self.on && self.big_bang
+    }
+
+    pub fn report_reject_transaction(&self) -> bool {
+        self.on && self.reject_transaction
+    }
+
+    pub fn report_invalid_block(&self) -> bool {
+        self.on && self.alarm_invalid_block
+    }
+
     pub fn update(&mut self, prop: &HashMap<String, String>) -> bool {
         let mut updated = self.collector_ep.update(prop);
         if self.on != self.collector_ep.is_set {
@@ -11,14 +11,33 @@ mod endpoint;
 mod api;
 mod events;
 mod sync;
+pub use self::sync::Data as SyncConfig;
 mod sql;
 mod conveyer;
 mod logger;
+mod profile;
+pub use profile::Profile;
 
 pub struct Config {
 	// [params]
 	pub node_id: String,
+	pub network: &'static Profile,
+	network_uuid: u64,
 	pub hosts_filename: String,
+	pub data_dir: String,
+	/// period to dump neighbours into data_dir, 0 = never
+	pub neighbours_dump_sec: u64,
+	/// round is stalled if no new one during factor * average round duration, 0 = never
+	pub round_stall_factor: u32,
+	/// request round table from neighbours if round is stalled
+	pub round_stall_request: bool,
+	/// base58 encoded keys authorized to stop outdated nodes, comma separated
+	pub stop_request_keys: String,
+	/// stored blocks verification at start: off, fast (the last verify_tail_blocks only, 1 at least) or full
+	pub verify_blocks: String,
+	pub verify_tail_blocks: u64,
+	/// truncate stored blocks to the last good one if verification fails
+	pub verify_truncate: bool,
 	bootstrap_type: String,
 	ipv6: bool,
 	pub min_compatible_version: u32,
@@ -34,13 +53,13 @@ pub struct Config {
 	// [host_input]
 	pub host_input: endpoint::Data,
 	// [pool_sync]
-	sync: sync::Data,
+	pub sync: sync::Data,
 	// [api]
 	api: api::Data,
 	// [conveyer]
-	conveyer: conveyer::Data,
+	pub conveyer: conveyer::Data,
 	// [event_report]
-	events: events::Data,
+	pub events: events::Data,
 	// [dbsql]
 	sql: sql::Data,
 	// logger
@@ -54,7 +73,17 @@ impl Config {
 	pub fn new(file_name: &str) -> Config {
 		let mut instance = Config {
 			node_id: String::from("AAExXjedndkJZrtPpJSX3taw5JB4sjqx32xWWWDnsKUu"),
+			network: &profile::TESTNET,
+			network_uuid: 0,
 			hosts_filename: String::new(),
+			data_dir: String::from("data"),
+			neighbours_dump_sec: 0,
+			round_stall_factor: 5,
+			round_stall_request: true,
+			stop_request_keys: String::new(),
+			verify_blocks: String::from("fast"),
+			verify_tail_blocks: 100,
+			verify_truncate: true,
 			bootstrap_type: String::from("start_node"),
 			ipv6: false,
 			min_compatible_version: 0,
@@ -79,43 +108,45 @@ impl Config {
 		instance
 	}
 
-	pub fn reload(&mut self) {
+	/// returns true if any parameter is updated
+	pub fn reload(&mut self) -> bool {
+		let mut updated = false;
 		match Ini::load_from_file(&self.ini_file) {
 			Ok(ini) => {
 				for (sec, prop) in ini.iter() {
 					match sec.as_ref().map(String::as_str) {
 						Some("params") => {
-							self.update(prop);
+							updated = self.update(prop) || updated;
 						}
 						Some("start_node") => {
-							self.start_node.update(prop);
+							updated = self.start_node.update(prop) || updated;
 						}
 						Some("host_input") => {
-							self.host_input.update(prop);
+							updated = self.host_input.update(prop) || updated;
 						}
 						Some("api") => {
-							self.api.update(prop);
+							updated = self.api.update(prop) || updated;
 						}
 						Some("conveyer") => {
-							self.conveyer.update(prop);
+							updated = self.conveyer.update(prop) || updated;
 						}
 						Some("pool_sync") => {
-							self.sync.update(prop);
+							updated = self.sync.update(prop) || updated;
 						}
 						Some("event_report") => {
-							self.events.update(prop);
+							updated = self.events.update(prop) || updated;
 						}
 						Some("dbsql") => {
-							self.sql.update(prop);
+							updated = self.sql.update(prop) || updated;
 						}
 						Some("Core") => {
-							self.logger.update_core(prop);
+							updated = self.logger.update_core(prop) || updated;
 						}
 						Some("Sinks.Console") => {
-							self.logger.update_console(prop);
+							updated = self.logger.update_console(prop) || updated;
 						}
 						Some("Sinks.File") => {
-							self.logger.update_file(prop);
+							updated = self.logger.update_file(prop) || updated;
 						}
 						//Some("Sinks.Event") => {}
 						Some(s) => {
@@ -127,6 +158,27 @@ impl Config {
 			}
 			Err(_) => ()
 		}
+		updated
+	}
+
+	/// blockchain UUID: explicitly set by network_uuid or the one of selected network profile
+	pub fn uuid(&self) -> u64 {
+		if self.network_uuid != 0 {
+			self.network_uuid
+		}
+		else {
+			self.network.uuid
+		}
+	}
+
+	/// min compatible node version: explicitly set by min_compatible_version or the one of selected network profile
+	pub fn min_version(&self) -> u16 {
+		if self.min_compatible_version > 0 {
+			self.min_compatible_version as u16
+		}
+		else {
+			self.network.min_version
+		}
 	}
 
 	fn update(&mut self, prop: &HashMap<String, String>) -> bool {
@@ -136,9 +188,45 @@ impl Config {
 				"node_id" => {
 					updated = try_update(&mut self.node_id, k, v) || updated;
 				}
+				"network" => {
+					if let Some(p) = profile::find(v) {
+						if p.name != self.network.name {
+							debug!("{} is updated: {} -> {}", k, self.network.name, p.name);
+							self.network = p;
+							updated = true;
+						}
+					}
+				}
+				"network_uuid" => {
+					updated = try_parse(&mut self.network_uuid, k, v) || updated;
+				}
 				"hosts_filename" => {
 					updated = try_update(&mut self.hosts_filename, k, v) || updated;
 				}
+				"data_dir" => {
+					updated = try_update(&mut self.data_dir, k, v) || updated;
+				}
+				"neighbours_dump_sec" => {
+					updated = try_parse(&mut self.neighbours_dump_sec, k, v) || updated;
+				}
+				"round_stall_factor" => {
+					updated = try_parse(&mut self.round_stall_factor, k, v) || updated;
+				}
+				"round_stall_request" => {
+					updated = try_parse(&mut self.round_stall_request, k, v) || updated;
+				}
+				"stop_request_keys" => {
+					updated = try_update(&mut self.stop_request_keys, k, v) || updated;
+				}
+				"verify_blocks" => {
+					updated = try_update(&mut self.verify_blocks, k, v) || updated;
+				}
+				"verify_tail_blocks" => {
+					updated = try_parse(&mut self.verify_tail_blocks, k, v) || updated;
+				}
+				"verify_truncate" => {
+					updated = try_parse(&mut self.verify_truncate, k, v) || updated;
+				}
 				"bootstrap_type" => {
 					updated = try_update(&mut self.bootstrap_type, k, v) || updated;
 				}
@@ -197,6 +285,27 @@ fn test_update_params() {
 	assert_eq!(conf.update(&data), false);
 }
 
+#[test]
+fn test_network_profile() {
+	let mut conf = Config::new("");
+	assert_eq!(conf.uuid(), 5283967947175248524);
+	let mut data = HashMap::<String, String>::new();
+	data.insert("network".to_string(), "local".to_string());
+	assert_eq!(conf.update(&data), true);
+	assert_eq!(conf.network.name, "local");
+	assert_eq!(conf.uuid(), profile::LOCAL.uuid);
+	data.insert("network_uuid".to_string(), "777".to_string());
+	assert_eq!(conf.update(&data), true);
+	assert_eq!(conf.uuid(), 777);
+
+	// mainnet uuid must be set explicitly
+	let mut conf = Config::new("");
+	let mut data = HashMap::<String, String>::new();
+	data.insert("network".to_string(), "mainnet".to_string());
+	assert_eq!(conf.update(&data), true);
+	assert_eq!(conf.uuid(), 0);
+}
+
 fn try_parse<N: FromStr + PartialEq + Copy + Display>(param: &mut N, key: &String, val: &String) -> bool {
 	match val.parse::<N>() {
 		Err(_) => {
@@ -0,0 +1,82 @@
+use log::warn;
+
+/// Parameters of the blockchain the node joins to
+pub struct Genesis {
+	/// initial trusted nodes (base58 encoded public keys) used until the first round table is received
+	pub trusted: &'static [&'static str]
+}
+
+/// Network profile bundles everything the node requires to join the certain blockchain
+pub struct Profile {
+	/// profile name as it is set by [params] network = ...
+	pub name: &'static str,
+	/// blockchain UUID, 0 means unknown and must be set explicitly by [params] network_uuid = ...
+	pub uuid: u64,
+	/// entry points (ip:port, base58 id) used if no known hosts file is set or it is unavailable
+	pub known_hosts: &'static [(&'static str, &'static str)],
+	/// genesis parameters
+	pub genesis: Genesis,
+	/// minimal node version compatible with the network
+	pub min_version: u16
+}
+
+pub static MAINNET: Profile = Profile {
+	name: "mainnet",
+	uuid: 0,
+	known_hosts: &[],
+	genesis: Genesis {
+		trusted: &[]
+	},
+	min_version: 502
+};
+
+pub static TESTNET: Profile = Profile {
+	name: "testnet",
+	uuid: 5283967947175248524,
+	known_hosts: &[
+		("195.133.147.58:9000", "HBxj19cnpayn46GSqBGyKQXMaLThH4quuPt5gf8aFndg"),
+		("165.22.253.11:9000", "EzhkcA8Q6eA4sWRTMQJquGkKLCRVUp94XiDwFspbwPHe"),
+		("68.183.230.109:9000", "Hh9gqQ6NSuVToXKjgz5FJoUD5Be9wu3Z3w5PE4HJwjk8"),
+		("68.183.181.120:9000", "DxwJgmx5JezEpc3vRNRXcrvaxN2Bzxav7xwiVChgjHrB"),
+		("165.22.242.197:9000", "23Atj7oiDU8XK1Wc7rMHvFnKQM5LRpttyn4zV8rbKw83"),
+		("165.22.250.42:9000", "8aMJCJCNE3hmYxbbKtqsQz4ujDtzZXpBAxpi3PdEQCxR"),
+		("178.128.101.90:9000", "4zWvvfJ1qnHj8WNWy6AT1qvibHnCaac7d99R6QQgRF86")
+	],
+	genesis: Genesis {
+		trusted: &[]
+	},
+	min_version: 502
+};
+
+pub static LOCAL: Profile = Profile {
+	name: "local",
+	uuid: 1,
+	known_hosts: &[
+		("127.0.0.1:9014", "HQ3qUgdyGySyjnNQ27kWQ5CtVC9mU51CAX5XtYkjKQL3")
+	],
+	genesis: Genesis {
+		trusted: &["HQ3qUgdyGySyjnNQ27kWQ5CtVC9mU51CAX5XtYkjKQL3"]
+	},
+	min_version: 0
+};
+
+pub fn find(name: &str) -> Option<&'static Profile> {
+	match name.to_lowercase().as_str() {
+		"mainnet" | "main" => Some(&MAINNET),
+		"testnet" | "test" => Some(&TESTNET),
+		"local" | "devnet" => Some(&LOCAL),
+		_ => {
+			warn!("unknown network profile {}, must be one of mainnet, testnet or local", name);
+			None
+		}
+	}
+}
+
+#[test]
+fn test_find_profile() {
+	assert_eq!(find("testnet").unwrap().uuid, 5283967947175248524);
+	assert_eq!(find("TestNet").unwrap().name, "testnet");
+	assert_eq!(find("devnet").unwrap().name, "local");
+	assert_eq!(find("main").unwrap().name, "mainnet");
+	assert!(find("unknown").is_none());
+}
@@ -3,17 +3,19 @@ use super::try_parse;
 
 pub struct Data {
 	/// true: sendBlockRequest one pool at a time; false: equal to number of pools requested
-	single_block_reply: bool, // = true;                
+	pub single_block_reply: bool, // = true;                
 	/// true: is silent mode synchro (sync up to the current round); false: normal mode      
-	fast_mode: bool, // = false;                    
+	pub fast_mode: bool, // = false;                    
 	/// max block count in one request: cannot be 0    
-	max_block_request: u8, // = 25;                 
+	pub max_block_request: u8, // = 25;                 
 	/// round count to repeat request, 0 = never  
-	request_round_delay: u8, // = 20;           
+	pub request_round_delay: u8, // = 20;           
 	/// max packet count to connect to another neighbor, 0 = never
-	max_neighbour_req_count: u8, // = 10;          
+	pub max_neighbour_req_count: u8, // = 10;          
+	/// max count of neighbours requested at once per update: cannot be 0
+	pub parallel_requests: u8, // = 1;
 	/// delay between updates of required block sequences, 0 = never, 1 = once per round, other value = delay in msec 
-    update_required_blocks_delay: u16 // = 350;  
+    pub update_required_blocks_delay: u16 // = 350;  
 }
 
 impl Data {
@@ -25,6 +27,7 @@ impl Data {
 			max_block_request: 25,
 			request_round_delay: 20,
 			max_neighbour_req_count: 10,
+			parallel_requests: 1,
 			update_required_blocks_delay: 350
 		}
 	}
@@ -48,6 +51,9 @@ impl Data {
 				"neighbour_packets_count" => {
 					updated = try_parse(&mut self.max_neighbour_req_count, k, v) || updated;
 				}
+				"parallel_requests_count" => {
+					updated = try_parse(&mut self.parallel_requests, k, v) || updated;
+				}
 				"sequences_verification_frequency" => {
 					updated = try_parse(&mut self.update_required_blocks_delay, k, v) || updated;
 				}
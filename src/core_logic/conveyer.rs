@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+
+use log::debug;
+
+use super::round_table::PacketHash;
+use super::super::csdb::TransactionsPacket;
+
+struct StoredPacket {
+    packet: TransactionsPacket,
+    /// packet as received
+    bytes: Vec<u8>,
+    /// the latest round the packet was received or referenced by round table
+    round: u64
+}
+
+/// Round-scoped store of transaction packets indexed by packet hash
+pub struct Conveyer {
+    packets: HashMap<PacketHash, StoredPacket>,
+    /// packets requested from peers: hash -> round of request
+    requested: HashMap<PacketHash, u64>
+}
+
+impl Conveyer {
+
+    pub fn new() -> Conveyer {
+        Conveyer {
+            packets: HashMap::new(),
+            requested: HashMap::new()
+        }
+    }
+
+    /// stores packet received in round rnd, returns false if it is already stored
+    pub fn store(&mut self, rnd: u64, packet: TransactionsPacket, bytes: &[u8]) -> bool {
+        self.requested.remove(&packet.hash);
+        if let Some(stored) = self.packets.get_mut(&packet.hash) {
+            stored.round = std::cmp::max(stored.round, rnd);
+            return false;
+        }
+        self.packets.insert(packet.hash, StoredPacket {
+            packet: packet,
+            bytes: bytes.to_vec(),
+            round: rnd
+        });
+        true
+    }
+
+    pub fn get(&self, hash: &PacketHash) -> Option<&TransactionsPacket> {
+        self.packets.get(hash).map(|p| &p.packet)
+    }
+
+    pub fn get_bytes(&self, hash: &PacketHash) -> Option<&[u8]> {
+        self.packets.get(hash).map(|p| &p.bytes[..])
+    }
+
+    /// marks packets referenced by round table of rnd, returns hashes of packets we do not have
+    pub fn reference(&mut self, rnd: u64, hashes: &[PacketHash]) -> Vec<PacketHash> {
+        let mut missing = Vec::<PacketHash>::new();
+        for h in hashes {
+            match self.packets.get_mut(h) {
+                None => missing.push(*h),
+                Some(p) => p.round = std::cmp::max(p.round, rnd)
+            }
+        }
+        missing
+    }
+
+    /// packets are requested in round rnd
+    pub fn request(&mut self, rnd: u64, hashes: &[PacketHash]) {
+        for h in hashes {
+            self.requested.insert(*h, rnd);
+        }
+    }
+
+    pub fn is_requested(&self, hash: &PacketHash) -> bool {
+        self.requested.contains_key(hash)
+    }
+
+    /// drops packets not referenced and requests not replied during ttl rounds before rnd
+    pub fn evict(&mut self, rnd: u64, ttl: u64) {
+        self.requested.retain(|_, r| *r + ttl > rnd);
+        let before = self.packets.len();
+        self.packets.retain(|_, p| p.round + ttl > rnd);
+        let evicted = before - self.packets.len();
+        if evicted > 0 {
+            debug!("{} transaction packets are evicted in R {}, {} left", evicted, rnd, self.packets.len());
+        }
+    }
+}
+
+#[test]
+fn test_conveyer() {
+    let packet = TransactionsPacket::new(vec![super::super::csdb::test_transaction()]);
+    let hash = packet.hash;
+    let other = super::super::csdb::Hash([5u8; 32]);
+    let mut conveyer = Conveyer::new();
+    assert!(conveyer.store(10, packet.clone(), &packet.to_bytes()));
+    assert!(!conveyer.store(10, packet.clone(), &packet.to_bytes()));
+    assert_eq!(conveyer.get_bytes(&hash), Some(&packet.to_bytes()[..]));
+
+    assert_eq!(conveyer.reference(12, &[hash, other]), vec![other]);
+    conveyer.request(12, &[other]);
+    assert!(conveyer.is_requested(&other));
+    conveyer.evict(21, 10);
+    assert!(conveyer.get(&hash).is_some());
+    conveyer.evict(22, 10);
+    assert!(conveyer.get(&hash).is_none());
+    assert!(!conveyer.is_requested(&other));
+}
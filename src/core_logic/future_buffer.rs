@@ -0,0 +1,92 @@
+use std::collections::BTreeMap;
+
+use log::debug;
+
+use super::super::PublicKey;
+use super::super::network::packet::MsgType;
+
+/// max count of rounds ahead of the current one to buffer messages for
+const MAX_ROUNDS_AHEAD: u64 = 5;
+/// max total count of buffered messages
+const MAX_BUFFERED: usize = 1024;
+
+pub struct BufferedMessage {
+    pub sender: PublicKey,
+    pub msg: MsgType,
+    pub bytes: Option<Vec<u8>>
+}
+
+/// Holds messages of future rounds until their round tables arrive
+pub struct FutureBuffer {
+    rounds: BTreeMap<u64, Vec<BufferedMessage>>,
+    count: usize,
+    /// messages too far ahead or exceeding the buffer
+    dropped_ahead: u64,
+    /// messages whose round table never arrived
+    dropped_expired: u64
+}
+
+impl FutureBuffer {
+
+    pub fn new() -> FutureBuffer {
+        FutureBuffer {
+            rounds: BTreeMap::<u64, Vec<BufferedMessage>>::new(),
+            count: 0,
+            dropped_ahead: 0,
+            dropped_expired: 0
+        }
+    }
+
+    /// buffers message of round rnd > cur, returns false if it is dropped
+    pub fn push(&mut self, cur: u64, sender: &PublicKey, msg: MsgType, rnd: u64, bytes: Option<&[u8]>) -> bool {
+        if rnd > cur + MAX_ROUNDS_AHEAD || self.count >= MAX_BUFFERED {
+            self.dropped_ahead += 1;
+            debug!("{}[{}] is too far ahead of R {}, drop (total {})", msg.to_string(), rnd, cur, self.dropped_ahead);
+            return false;
+        }
+        self.rounds.entry(rnd).or_insert_with(Vec::new).push(BufferedMessage {
+            sender: *sender,
+            msg: msg,
+            bytes: bytes.map(|b| b.to_vec())
+        });
+        self.count += 1;
+        true
+    }
+
+    /// takes messages of round rnd in order they were received, messages of earlier rounds are dropped
+    pub fn take(&mut self, rnd: u64) -> Vec<BufferedMessage> {
+        let later = self.rounds.split_off(&(rnd + 1));
+        let mut current = std::mem::replace(&mut self.rounds, later);
+        let messages = current.remove(&rnd).unwrap_or_default();
+        let expired: usize = current.values().map(|v| v.len()).sum();
+        if expired > 0 {
+            self.dropped_expired += expired as u64;
+            debug!("{} messages of rounds before R {} never got round table, drop (total {})", expired, rnd, self.dropped_expired);
+        }
+        self.count -= expired + messages.len();
+        messages
+    }
+
+    pub fn dropped(&self) -> (u64, u64) {
+        (self.dropped_ahead, self.dropped_expired)
+    }
+}
+
+#[test]
+fn test_future_buffer() {
+    let sender = [1u8; 32];
+    let mut buf = FutureBuffer::new();
+    assert!(buf.push(10, &sender, MsgType::FirstStage, 11, Some(&[1u8])));
+    assert!(buf.push(10, &sender, MsgType::SecondStage, 12, Some(&[2u8])));
+    assert!(buf.push(10, &sender, MsgType::ThirdStage, 12, None));
+    assert!(buf.push(10, &sender, MsgType::FirstStage, 13, None));
+    assert!(!buf.push(10, &sender, MsgType::FirstStage, 10 + MAX_ROUNDS_AHEAD + 1, None));
+
+    let messages = buf.take(12);
+    assert_eq!(messages.len(), 2);
+    assert_eq!(messages[0].bytes, Some(vec![2u8]));
+    assert!(messages[1].bytes.is_none());
+    assert_eq!(buf.dropped(), (1, 1));
+    assert_eq!(buf.take(13).len(), 1);
+    assert_eq!(buf.count, 0);
+}
@@ -0,0 +1,128 @@
+use std::collections::HashSet;
+
+use super::super::{Hash, PublicKey};
+
+/// count of consecutive rounds the trusted nodes disagree with our blocks to flag a fork
+pub const PERSISTENT_MISMATCH_ROUNDS: u32 = 3;
+
+/// reply disagreeing with our block
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mismatch {
+    pub sender: PublicKey,
+    pub sequence: u64,
+    pub hash: Hash
+}
+
+/// Collects HashReplies of trusted nodes and tracks how long they disagree with our blocks
+pub struct HashReplies {
+    round: u64,
+    /// count of trusted nodes of the round
+    trusted: usize,
+    matched: HashSet<PublicKey>,
+    mismatched: HashSet<PublicKey>,
+    /// the lowest block the current round replies disagree with
+    mismatch: Option<Mismatch>,
+    /// count of consecutive rounds the majority of trusted nodes disagree with us
+    mismatch_rounds: u32
+}
+
+impl HashReplies {
+
+    pub fn new() -> HashReplies {
+        HashReplies {
+            round: 0,
+            trusted: 0,
+            matched: HashSet::new(),
+            mismatched: HashSet::new(),
+            mismatch: None,
+            mismatch_rounds: 0
+        }
+    }
+
+    /// reply of one of trusted nodes of round rnd is compared with our block, the first reply of the node counts only
+    pub fn on_reply(&mut self, rnd: u64, trusted: usize, matches: bool, sender: &PublicKey, sequence: u64, hash: &Hash) {
+        if rnd != self.round {
+            self.clear_round();
+            self.round = rnd;
+            self.trusted = trusted;
+        }
+        if self.matched.contains(sender) || self.mismatched.contains(sender) {
+            return;
+        }
+        if matches {
+            self.matched.insert(*sender);
+            return;
+        }
+        self.mismatched.insert(*sender);
+        if self.mismatch.as_ref().map(|m| sequence < m.sequence).unwrap_or(true) {
+            self.mismatch = Some(Mismatch {
+                sender: *sender,
+                sequence: sequence,
+                hash: *hash
+            });
+        }
+    }
+
+    /// the round is over, returns the mismatch of it if the majority of trusted nodes disagree with us
+    /// PERSISTENT_MISMATCH_ROUNDS rounds
+    pub fn end_round(&mut self) -> Option<Mismatch> {
+        // rounds without replies do not change anything
+        if self.matched.is_empty() && self.mismatched.is_empty() {
+            return None;
+        }
+        let disagree = self.mismatched.len() >= self.trusted / 2 + 1;
+        let mismatch = self.mismatch.take();
+        self.clear_round();
+        if !disagree {
+            self.mismatch_rounds = 0;
+            return None;
+        }
+        self.mismatch_rounds += 1;
+        if self.mismatch_rounds < PERSISTENT_MISMATCH_ROUNDS {
+            return None;
+        }
+        self.mismatch_rounds = 0;
+        mismatch
+    }
+
+    fn clear_round(&mut self) {
+        self.matched.clear();
+        self.mismatched.clear();
+        self.mismatch = None;
+    }
+}
+
+#[test]
+fn test_hash_replies() {
+    let a = [1u8; 32];
+    let b = [2u8; 32];
+    let c = [3u8; 32];
+    let mut replies = HashReplies::new();
+    for rnd in 10..10 + PERSISTENT_MISMATCH_ROUNDS as u64 - 1 {
+        replies.on_reply(rnd, 3, false, &a, 7, &Hash([7u8; 32]));
+        replies.on_reply(rnd, 3, false, &b, 5, &Hash([5u8; 32]));
+        replies.on_reply(rnd, 3, true, &c, 4, &Hash([4u8; 32]));
+        assert_eq!(replies.end_round(), None);
+    }
+    // the round without replies, then the agreeing one resets the count
+    assert_eq!(replies.end_round(), None);
+    replies.on_reply(21, 3, true, &a, 7, &Hash([7u8; 32]));
+    assert_eq!(replies.end_round(), None);
+
+    // the only reply of three trusted nodes is not the majority, repeated replies do not count
+    for rnd in 22..22 + PERSISTENT_MISMATCH_ROUNDS as u64 {
+        replies.on_reply(rnd, 3, false, &b, 5, &Hash([5u8; 32]));
+        replies.on_reply(rnd, 3, false, &b, 5, &Hash([5u8; 32]));
+        assert_eq!(replies.end_round(), None);
+    }
+
+    let mut flagged = None;
+    for rnd in 30..30 + PERSISTENT_MISMATCH_ROUNDS as u64 {
+        // the mismatch of the previous round is forgotten
+        let sequence = if rnd == 30 { 3 } else { 5 };
+        replies.on_reply(rnd, 3, false, &a, sequence, &Hash([5u8; 32]));
+        replies.on_reply(rnd, 3, false, &b, 5, &Hash([5u8; 32]));
+        flagged = replies.end_round();
+    }
+    assert_eq!(flagged, Some(Mismatch { sender: a, sequence: 5, hash: Hash([5u8; 32]) }));
+}
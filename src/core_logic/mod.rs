@@ -1,62 +1,164 @@
 use std::sync::mpsc::Sender;
+use std::convert::TryInto;
+use std::path::PathBuf;
+use std::time::Instant;
 
-use log::{debug, info};
+use log::{debug, info, warn, error};
 
 use super::config::SharedConfig;
-use super::PublicKey;
+use super::node_state::{SharedState, SyncState, Role};
+use super::event_bus::{Event, SharedBus};
+use super::storage::{Storage, SharedStorage};
+use super::storage::verify::verify_signatures;
+use super::sync::{SyncStateMachine, PoolSync, PendingBlocks, BlockServer, pack_block_request, parse_block_request, parse_blocks};
+use super::collaboration::NeighboursView;
+use super::{PublicKey, PUBLIC_KEY_SIZE, HASH_SIZE, NODE_VERSION};
 use super::network::packet::{Packet, MsgType};
+use super::csdb::{Hash, Pool, TransactionsPacket, Wallets};
 
 mod round;
 use round::Round;
+mod round_table;
+use round_table::{RoundTable, PacketHash};
+mod round_history;
+mod future_buffer;
+use future_buffer::FutureBuffer;
+mod stage_cache;
+use stage_cache::{Stage, StageCache};
+mod role;
+use role::RoleMachine;
+mod conveyer;
+use conveyer::Conveyer;
+mod validator;
+use validator::Validator;
+pub use validator::RejectReason;
+mod rollback;
+use rollback::{Rollback, FORK_CONFIRMATIONS, MAX_ROLLBACK_BLOCKS, write_audit};
+mod hash_replies;
+use hash_replies::HashReplies;
+
+extern crate base58;
+use base58::{FromBase58, ToBase58};
+
+/// file in data_dir the rollbacks are logged into
+const ROLLBACK_LOG_FILE: &str = "rollback.log";
 
 pub struct CoreLogic {
     tx_send: Sender<Packet>,
     config: SharedConfig,
-    round: Round
+    state: SharedState,
+    bus: SharedBus,
+    storage: SharedStorage,
+    neighbours: NeighboursView,
+    round: Round,
+    role: RoleMachine,
+    sync: SyncStateMachine,
+    // requests missing blocks
+    pool_sync: PoolSync,
+    // blocks received ahead of the next required one
+    pending: PendingBlocks,
+    // serves blocks to others
+    block_server: BlockServer,
+    // time point the round stall was reported last time
+    stall_reported: Option<Instant>,
+    // the latest round the table is requested for
+    table_requested: u64,
+    // messages of future rounds
+    future: FutureBuffer,
+    // stage messages of the last rounds
+    stages: StageCache,
+    // wallet state transactions are validated against
+    wallets: Wallets,
+    // transaction packets of the last rounds
+    conveyer: Conveyer,
+    // undo of the last blocks and fork suspicions
+    rollback: Rollback,
+    // hashes of the last blocks trusted nodes reply
+    hash_replies: HashReplies
+}
+
+enum RoundTest {
+    Process,
+    Buffer,
+    Drop
 }
 
 impl CoreLogic {
-    pub fn new(conf: SharedConfig, tx_send: Sender<Packet>) -> CoreLogic {
+    pub fn new(conf: SharedConfig, state: SharedState, bus: SharedBus, storage: SharedStorage, view: NeighboursView, tx_send: Sender<Packet>) -> CoreLogic {
+        let own_key = parse_public_key(&conf.read().unwrap().node_id);
+        let (wallets, rollback) = load_wallets(&storage.read().unwrap());
         CoreLogic {
             tx_send: tx_send,
             config: conf,
-            round: Round::new()
+            state: state,
+            bus: bus,
+            storage: storage,
+            neighbours: view,
+            round: Round::new(),
+            role: RoleMachine::new(own_key),
+            sync: SyncStateMachine::new(),
+            pool_sync: PoolSync::new(),
+            pending: PendingBlocks::new(),
+            block_server: BlockServer::new(),
+            stall_reported: None,
+            table_requested: 0,
+            future: FutureBuffer::new(),
+            stages: StageCache::new(),
+            wallets: wallets,
+            conveyer: Conveyer::new(),
+            rollback: rollback,
+            hash_replies: HashReplies::new()
         }
     }
 
     pub fn handle(&mut self, sender: &PublicKey, msg: MsgType, rnd: u64, bytes: Option<&[u8]>) {
-        if !self.test_packet_round(rnd, &msg) {
+        match self.test_packet_round(sender, rnd, &msg) {
+            RoundTest::Process => (),
+            RoundTest::Buffer => {
+                let cur = self.round.current();
+                if self.future.push(cur, sender, msg, rnd, bytes) {
+                    debug!("msg of R {} is buffered until its round table", rnd);
+                }
+                return;
+            }
+            RoundTest::Drop => {
+                debug!("{}[{}] is outdated, drop", msg.to_string(), rnd);
+                return;
+            }
+        }
+        if !self.role.is_active(&msg) {
+            debug!("{} is not handled by {} node", msg.to_string(), self.role.role());
             return;
         }
         match msg {
             MsgType::BootstrapTable => self.handle_bootstrap_table(sender, rnd, bytes),
             // MsgType::Transactions,
             // MsgType::FirstTransaction,
-            // MsgType::NewBlock,
-            // MsgType::BlockHash,
-            // MsgType::BlockRequest,
-            // MsgType::RequestedBlock,
-            // MsgType::FirstStage,
-            // MsgType::SecondStage,
-            // MsgType::ThirdStage,
-            // MsgType::FirstStageRequest,
-            // MsgType::SecondStageRequest,
-            // MsgType::ThirdStageRequest,
-            // MsgType::RoundTableRequest,
-            // MsgType::RoundTableReply,
+            MsgType::NewBlock => self.handle_new_block(sender, rnd, bytes),
+            MsgType::BlockHash => self.handle_block_hash(sender, rnd, bytes),
+            MsgType::BlockRequest => self.handle_block_request(sender, rnd, bytes),
+            MsgType::RequestedBlock => self.handle_requested_blocks(sender, rnd, bytes),
+            MsgType::FirstStage |
+            MsgType::SecondStage |
+            MsgType::ThirdStage => self.handle_stage(sender, msg, rnd, bytes),
+            MsgType::FirstStageRequest |
+            MsgType::SecondStageRequest |
+            MsgType::ThirdStageRequest => self.handle_stage_request(sender, msg, rnd, bytes),
+            MsgType::RoundTableRequest => self.handle_round_table_request(sender, rnd, bytes),
+            MsgType::RoundTableReply => self.handle_round_table(sender, rnd, bytes),
             MsgType::TransactionPacket => self.handle_transaction_packet(sender, rnd, bytes),
-            // MsgType::TransactionsPacketRequest,
-            // MsgType::TransactionsPacketReply,
+            MsgType::TransactionsPacketRequest => self.handle_packets_request(sender, rnd, bytes),
+            MsgType::TransactionsPacketReply => self.handle_packets_reply(sender, rnd, bytes),
             // MsgType::NewCharacteristic,
             // MsgType::WriterNotification,
-            // MsgType::FirstSmartStage,
-            // MsgType::SecondSmartStage,
+            MsgType::FirstSmartStage |
+            MsgType::SecondSmartStage => self.handle_stage(sender, msg, rnd, bytes),
             MsgType::RoundTable => self.handle_round_table(sender, rnd, bytes),
-            // MsgType::ThirdSmartStage,
-            // MsgType::SmartFirstStageRequest,
-            // MsgType::SmartSecondStageRequest,
-            // MsgType::SmartThirdStageRequest,
-            // MsgType::HashReply,
+            MsgType::ThirdSmartStage => self.handle_stage(sender, msg, rnd, bytes),
+            MsgType::SmartFirstStageRequest |
+            MsgType::SmartSecondStageRequest |
+            MsgType::SmartThirdStageRequest => self.handle_stage_request(sender, msg, rnd, bytes),
+            MsgType::HashReply => self.handle_hash_reply(sender, rnd, bytes),
             // MsgType::RejectedContracts,
             // MsgType::RoundPackRequest,
             // MsgType::StateRequest,
@@ -70,29 +172,1037 @@ impl CoreLogic {
         }
     }
 
-    fn test_packet_round(&self, rnd: u64, msg: &MsgType) -> bool {
+    /// called periodically by message processor
+    pub fn on_timer(&mut self) {
+        self.test_round_stall();
+        let delay = self.config.read().unwrap().sync.update_required_blocks_delay;
+        if self.is_syncing() && self.pool_sync.is_update_time(delay) {
+            self.sync_blocks();
+        }
+    }
+
+    fn test_round_stall(&mut self) {
+        let factor;
+        let request_table;
+        {
+            let conf_guard = self.config.read().unwrap();
+            factor = conf_guard.round_stall_factor;
+            request_table = conf_guard.round_stall_request;
+        }
+        if !self.round.is_stalled(factor) {
+            self.stall_reported = None;
+            return;
+        }
+        // repeat report every stall period
+        if let Some(t) = self.stall_reported {
+            if !self.round.is_stalled_since(t, factor) {
+                return;
+            }
+        }
+        self.stall_reported = Some(Instant::now());
+        let elapsed = self.round.elapsed();
+        match self.round.history().last() {
+            Some(r) => {
+                warn!("round {} is stalled: no new round during {} ms, {} trusted, table from {}",
+                    r.round, elapsed, r.trusted, r.initiator.to_base58());
+            }
+            None => {
+                warn!("round {} is stalled: no new round during {} ms", self.round.current(), elapsed);
+            }
+        }
+        if request_table {
+            self.send_round_table_request(None, self.round.current() + 1);
+        }
+    }
+
+    fn send_round_table_request(&self, target: Option<&PublicKey>, rnd: u64) {
+        match Packet::new_message(target, MsgType::RoundTableRequest, rnd, &[]) {
+            None => {
+                error!("failed to create round table request");
+            }
+            Some(pack) => {
+                match self.tx_send.send(pack) {
+                    Err(e) => {
+                        warn!("failed send round table request: {}", e);
+                    }
+                    Ok(_) => {
+                        debug!("transfer round table request for R {}", rnd);
+                    }
+                }
+            }
+        }
+    }
+
+    fn test_packet_round(&mut self, sender: &PublicKey, rnd: u64, msg: &MsgType) -> RoundTest {
         let cur = self.round.current();
-        match msg {
+        let pass = match msg {
             MsgType::BootstrapTable => { rnd >= cur },
-            _ => rnd > cur
+            MsgType::RoundTable | MsgType::RoundTableReply => rnd > cur,
+            MsgType::RoundTableRequest |
+            MsgType::TransactionsPacketRequest |
+            MsgType::TransactionsPacketReply |
+            MsgType::BlockRequest |
+            MsgType::RequestedBlock |
+            MsgType::NodeStopRequest => true,
+            // stages of the past rounds are replied while they are cached
+            _ if rnd < cur && Stage::from_request(msg).is_some() => StageCache::keeps(rnd, cur),
+            _ => {
+                if rnd > cur {
+                    if rnd > self.table_requested {
+                        // message of the round we have no table for yet
+                        debug!("no round table for R {} yet, request it from {}", rnd, sender.to_base58());
+                        self.table_requested = rnd;
+                        self.send_round_table_request(Some(sender), rnd);
+                    }
+                    return RoundTest::Buffer;
+                }
+                rnd == cur
+            }
+        };
+        if pass {
+            RoundTest::Process
+        }
+        else {
+            RoundTest::Drop
+        }
+    }
+
+    fn handle_stage(&mut self, sender: &PublicKey, msg: MsgType, rnd: u64, bytes: Option<&[u8]>) {
+        let stage = match Stage::from_msg(&msg) {
+            None => return,
+            Some(s) => s
+        };
+        let payload = match bytes {
+            None => {
+                warn!("malformed {}: no payload", msg.to_string());
+                return;
+            }
+            Some(v) => v
+        };
+        let index = match self.round.table().and_then(|t| t.confidant_index(sender)) {
+            None => {
+                debug!("{} from not trusted {}, drop", msg.to_string(), sender.to_base58());
+                return;
+            }
+            Some(i) => i as u8
+        };
+        if !self.stages.store(rnd, stage, index, payload) {
+            return;
+        }
+        if stage == Stage::Third {
+            self.update_writer(index, payload);
         }
     }
 
-    fn handle_bootstrap_table(&self, _sender: &PublicKey, _rnd: u64, _bytes: Option<&[u8]>) {
+    /// third stage names the writer of the round
+    fn update_writer(&mut self, sender: u8, payload: &[u8]) {
+        /*
+            subRound(1) + signature(64) + sender(1) + writer(1) + ...
+        */
+        let writer = match payload.get(1 + 64 + 1) {
+            None => {
+                warn!("malformed third stage of [{}]", sender);
+                return;
+            }
+            Some(v) => *v
+        };
+        let changed = match self.round.table() {
+            None => None,
+            Some(t) => self.role.on_stage_three(t, sender, writer)
+        };
+        if let Some(r) = changed {
+            self.on_role_changed(r);
+        }
+    }
 
+    fn handle_stage_request(&self, sender: &PublicKey, msg: MsgType, rnd: u64, bytes: Option<&[u8]>) {
+        /*
+            regular: subRound(1) + required(1)
+            smart: smartID(8) + respondent(1) + required(1)
+        */
+        let stage = match Stage::from_request(&msg) {
+            None => return,
+            Some(s) => s
+        };
+        let required = match bytes.and_then(|b| b.last()) {
+            None => {
+                warn!("malformed {}: no payload", msg.to_string());
+                return;
+            }
+            Some(v) => *v
+        };
+        match self.stages.get(rnd, stage, required) {
+            None => {
+                debug!("no {:?} stage of [{}] in R {} to reply {}", stage, required, rnd, sender.to_base58());
+            }
+            Some(messages) => {
+                for payload in messages {
+                    match Packet::new_message(Some(sender), stage.msg(), rnd, payload) {
+                        None => {
+                            error!("failed to create stage reply");
+                        }
+                        Some(pack) => {
+                            match self.tx_send.send(pack) {
+                                Err(e) => {
+                                    warn!("failed send stage reply: {}", e);
+                                }
+                                Ok(_) => {
+                                    debug!("transfer {:?} stage of [{}] to {}", stage, required, sender.to_base58());
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
     }
 
-    fn handle_transaction_packet(&self, _sender: &PublicKey, _rnd: u64, _bytes: Option<&[u8]>) {
-        
+    fn handle_round_table_request(&self, sender: &PublicKey, rnd: u64, _bytes: Option<&[u8]>) {
+        match self.round.cached_table(rnd) {
+            None => {
+                debug!("no round table for R {} to reply {}", rnd, sender.to_base58());
+            }
+            Some(payload) => {
+                match Packet::new_message(Some(sender), MsgType::RoundTableReply, rnd, payload) {
+                    None => {
+                        error!("failed to create round table reply");
+                    }
+                    Some(pack) => {
+                        match self.tx_send.send(pack) {
+                            Err(e) => {
+                                warn!("failed send round table reply: {}", e);
+                            }
+                            Ok(_) => {
+                                debug!("transfer round table R {} to {}", rnd, sender.to_base58());
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn handle_bootstrap_table(&mut self, sender: &PublicKey, rnd: u64, bytes: Option<&[u8]>) {
+        let mut table = match bytes {
+            None => {
+                warn!("malformed bootstrap table: no payload");
+                return;
+            }
+            Some(input) => match RoundTable::from_bootstrap_bytes(rnd, input) {
+                Err(e) => {
+                    warn!("failed to unpack bootstrap table: {}", e);
+                    return;
+                }
+                Ok(t) => t
+            }
+        };
+        let big_bang;
+        {
+            let conf_guard = self.config.read().unwrap();
+            if table.confidants.is_empty() {
+                // start with genesis trusted nodes of the network
+                table.confidants = conf_guard.network.genesis.trusted.iter().map(|k| parse_public_key(k)).collect();
+            }
+            big_bang = conf_guard.events.report_big_bang();
+        }
+        info!("bootstrap table of R {} from {}, trusted: {}", rnd, sender.to_base58(), table.confidants.len());
+
+        let prev_role = self.role.role();
+        if !self.round.start(sender, table, None, &mut self.role) {
+            return;
+        }
+        self.on_round_started(rnd, prev_role);
+        if big_bang {
+            self.bus.publish(Event::BigBang(rnd));
+        }
     }
 
-    fn handle_round_table(&mut self, _sender: &PublicKey, rnd: u64, bytes: Option<&[u8]>) {
-        if !self.round.handle_table(rnd, bytes) {
-            info!("failed to handle round table")
+    fn handle_transaction_packet(&mut self, sender: &PublicKey, _rnd: u64, bytes: Option<&[u8]>) {
+        let input = match bytes {
+            None => {
+                warn!("malformed transaction packet: no payload");
+                return;
+            }
+            Some(v) => v
+        };
+        let packet = match TransactionsPacket::from_bytes(input) {
+            Err(e) => {
+                warn!("failed to unpack transaction packet from {}: {}", sender.to_base58(), e);
+                return;
+            }
+            Ok(p) => p
+        };
+        let report = self.config.read().unwrap().events.report_reject_transaction();
+        let mut validator = Validator::new(&self.wallets);
+        let mut accepted = 0;
+        for t in &packet.transactions {
+            match validator.validate(t) {
+                Ok(_) => accepted += 1,
+                Err(reason) => {
+                    let hash = t.hash();
+                    debug!("transaction {} from packet {} is rejected: {}", hash, packet.hash, reason);
+                    if report {
+                        self.bus.publish(Event::TransactionRejected(hash, reason));
+                    }
+                }
+            }
+        }
+        if accepted < packet.transactions.len() {
+            debug!("transaction packet {} from {} is dropped: {} of {} transactions are valid",
+                packet.hash, sender.to_base58(), accepted, packet.transactions.len());
+            return;
         }
+        debug!("transaction packet {} from {}: {} transactions are valid", packet.hash, sender.to_base58(), accepted);
+        self.conveyer.store(self.round.current(), packet, input);
     }
 
-    fn handle_stop_request(&self, _sender: &PublicKey, _rnd: u64, _bytes: Option<&[u8]>) {
-        
+    fn handle_packets_request(&self, sender: &PublicKey, rnd: u64, bytes: Option<&[u8]>) {
+        let hashes = match bytes.map(|input| parse_packet_hashes(input)) {
+            Some(Ok(v)) => v,
+            _ => {
+                warn!("malformed transaction packets request from {}", sender.to_base58());
+                return;
+            }
+        };
+        /*
+            stream << packets.size();
+            for (const auto& packet : packets) {
+                stream << packet;
+            }
+            packet is streamed as its binary: size(8) + bytes
+        */
+        let mut payload = Vec::<u8>::new();
+        let mut count: u64 = 0;
+        for h in &hashes {
+            if let Some(packet) = self.conveyer.get_bytes(h) {
+                payload.extend_from_slice(&(packet.len() as u64).to_le_bytes());
+                payload.extend_from_slice(packet);
+                count += 1;
+            }
+        }
+        if count == 0 {
+            debug!("no requested transaction packets to reply {}", sender.to_base58());
+            return;
+        }
+        let mut output = count.to_le_bytes().to_vec();
+        output.extend_from_slice(&payload);
+        match Packet::new_message(Some(sender), MsgType::TransactionsPacketReply, rnd, &output) {
+            None => {
+                error!("failed to create transaction packets reply");
+            }
+            Some(pack) => {
+                match self.tx_send.send(pack) {
+                    Err(e) => {
+                        warn!("failed send transaction packets reply: {}", e);
+                    }
+                    Ok(_) => {
+                        debug!("transfer {} of {} requested transaction packets to {}", count, hashes.len(), sender.to_base58());
+                    }
+                }
+            }
+        }
     }
+
+    fn handle_packets_reply(&mut self, sender: &PublicKey, _rnd: u64, bytes: Option<&[u8]>) {
+        /*
+            std::size_t packetsCount = 0;
+            stream >> packetsCount;
+            for (std::size_t i = 0; i < packetsCount; ++i) {
+                cs::TransactionsPacket packet;
+                stream >> packet;
+                packets.push_back(std::move(packet));
+            }
+        */
+        let mut input = match bytes {
+            Some(v) if v.len() >= 8 => v,
+            _ => {
+                warn!("malformed transaction packets reply: no payload");
+                return;
+            }
+        };
+        let count = u64::from_le_bytes(input[..8].try_into().unwrap());
+        input = &input[8..];
+        let rnd = self.round.current();
+        let mut stored = 0;
+        for _ in 0..count {
+            if input.len() < 8 {
+                warn!("malformed transaction packets reply from {}", sender.to_base58());
+                break;
+            }
+            let size = u64::from_le_bytes(input[..8].try_into().unwrap());
+            if (input.len() as u64 - 8) < size {
+                warn!("malformed transaction packets reply from {}", sender.to_base58());
+                break;
+            }
+            let bytes = &input[8..8 + size as usize];
+            input = &input[8 + size as usize..];
+            match TransactionsPacket::from_bytes(bytes) {
+                Err(e) => {
+                    warn!("failed to unpack requested transaction packet from {}: {}", sender.to_base58(), e);
+                }
+                Ok(packet) => {
+                    if !self.conveyer.is_requested(&packet.hash) {
+                        debug!("transaction packet {} from {} is not requested, drop", packet.hash, sender.to_base58());
+                        continue;
+                    }
+                    if self.conveyer.store(rnd, packet, bytes) {
+                        stored += 1;
+                    }
+                }
+            }
+        }
+        debug!("{} of {} transaction packets from {} are new", stored, count, sender.to_base58());
+        self.update_round_transactions();
+    }
+
+    /// references packets of the round table, requests missing ones and evicts outdated
+    fn update_round_packets(&mut self, rnd: u64) {
+        let hashes = match self.round.table() {
+            None => return,
+            Some(t) => t.hashes.clone()
+        };
+        let missing = self.conveyer.reference(rnd, &hashes);
+        if !missing.is_empty() {
+            self.send_packets_request(rnd, &missing);
+            self.conveyer.request(rnd, &missing);
+        }
+        let ttl = self.config.read().unwrap().conveyer.packet_ttl();
+        self.conveyer.evict(rnd, ttl);
+        self.update_round_transactions();
+    }
+
+    /// counts transactions of the current round packets we have
+    fn update_round_transactions(&mut self) {
+        let rnd = self.round.current();
+        let count = match self.round.table() {
+            None => return,
+            Some(t) => t.hashes.iter()
+                .filter_map(|h| self.conveyer.get(h))
+                .map(|p| p.transactions.len())
+                .sum()
+        };
+        self.round.history().set_transactions(rnd, count);
+    }
+
+    fn send_packets_request(&self, rnd: u64, hashes: &[PacketHash]) {
+        // the layout parse_packet_hashes() reads
+        let mut output = (hashes.len() as u64).to_le_bytes().to_vec();
+        for h in hashes {
+            output.extend_from_slice(h.as_bytes());
+        }
+        match Packet::new_message(None, MsgType::TransactionsPacketRequest, rnd, &output) {
+            None => {
+                error!("failed to create transaction packets request");
+            }
+            Some(pack) => {
+                match self.tx_send.send(pack) {
+                    Err(e) => {
+                        warn!("failed send transaction packets request: {}", e);
+                    }
+                    Ok(_) => {
+                        debug!("request {} missing transaction packets of R {}", hashes.len(), rnd);
+                    }
+                }
+            }
+        }
+    }
+
+    fn handle_round_table(&mut self, sender: &PublicKey, rnd: u64, bytes: Option<&[u8]>) {
+        let prev_role = self.role.role();
+        if !self.round.handle_table(sender, rnd, bytes, &mut self.role) {
+            return;
+        }
+        self.on_round_started(rnd, prev_role);
+    }
+
+    fn on_round_started(&mut self, rnd: u64, prev_role: Role) {
+        {
+            let mut guard = self.state.write().unwrap();
+            guard.round = self.round.current();
+        }
+        if self.role.role() != prev_role {
+            self.on_role_changed(self.role.role());
+        }
+        self.bus.publish(Event::RoundStarted(self.round.current()));
+        self.check_hash_replies();
+        if self.role.role() != Role::Normal {
+            self.send_hash_reply(rnd);
+        }
+        self.update_sync_state();
+        self.update_round_packets(rnd);
+        // replay messages received ahead of the round table
+        for m in self.future.take(rnd) {
+            self.handle(&m.sender, m.msg, rnd, m.bytes.as_ref().map(|v| &v[..]));
+        }
+        let (ahead, expired) = self.future.dropped();
+        if ahead + expired > 0 {
+            debug!("future messages dropped: {} too far ahead, {} without round table", ahead, expired);
+        }
+    }
+
+    fn update_sync_state(&mut self) {
+        let rnd = self.round.current();
+        let sequence;
+        let network_sequence;
+        let network_round;
+        {
+            let guard = self.state.read().unwrap();
+            sequence = guard.sequence;
+            network_sequence = guard.network_sequence;
+            network_round = guard.network_round;
+        }
+        if let Some(s) = self.sync.update(sequence, rnd, network_sequence, network_round) {
+            self.on_sync_state_changed(s);
+        }
+        if self.sync.state() == SyncState::Behind {
+            info!("start block synchronization from {} up to {}", sequence + 1, network_sequence);
+            if let Some(s) = self.sync.start_syncing(rnd) {
+                self.on_sync_state_changed(s);
+            }
+            self.sync_blocks();
+        }
+        else if self.is_syncing() && self.config.read().unwrap().sync.update_required_blocks_delay == 1 {
+            self.sync_blocks();
+        }
+    }
+
+    fn is_syncing(&self) -> bool {
+        match self.sync.state() {
+            SyncState::Syncing | SyncState::Stalled => true,
+            _ => false
+        }
+    }
+
+    /// requests required blocks from the best neighbours
+    fn sync_blocks(&mut self) {
+        self.pool_sync.on_update();
+        let rnd = self.round.current();
+        let from = self.storage.read().unwrap().last_sequence().map(|s| s + 1).unwrap_or(0);
+        let target = self.state.read().unwrap().network_sequence;
+        if from > target {
+            return;
+        }
+        let neighbours = self.neighbours.best_for_sync(from);
+        let requests;
+        {
+            let conf_guard = self.config.read().unwrap();
+            // scan no more blocks than the neighbours can be asked for at once
+            let last = std::cmp::min(target, from + PoolSync::scan_limit(&conf_guard.sync, neighbours.len()) - 1);
+            let ranges: Vec<(u64, u64)> = self.pending.missing_ranges(from, last).iter()
+                .flat_map(|(a, b)| self.pool_sync.required_ranges(&conf_guard.sync, *a, *b, rnd))
+                .collect();
+            if ranges.is_empty() {
+                return;
+            }
+            debug!("required blocks: {:?}", ranges);
+            requests = self.pool_sync.next_requests(&conf_guard.sync, &ranges, rnd, &neighbours);
+        }
+        for r in requests {
+            match Packet::new_message(Some(&r.target), MsgType::BlockRequest, rnd, &pack_block_request(&r.sequences, r.packet)) {
+                None => {
+                    error!("failed to create block request");
+                }
+                Some(pack) => {
+                    if let Err(e) = self.tx_send.send(pack) {
+                        warn!("failed send block request: {}", e);
+                    }
+                }
+            }
+        }
+    }
+
+    fn handle_block_request(&mut self, sender: &PublicKey, rnd: u64, bytes: Option<&[u8]>) {
+        let (sequences, packet) = match bytes.and_then(parse_block_request) {
+            None => {
+                warn!("malformed block request from {}", sender.to_base58());
+                return;
+            }
+            Some(v) => v
+        };
+        let single_block_reply = self.config.read().unwrap().sync.single_block_reply;
+        let replies = {
+            let storage = self.storage.read().unwrap();
+            self.block_server.replies(&storage, sender, &sequences, packet, single_block_reply)
+        };
+        if replies.is_empty() {
+            debug!("no requested blocks to reply {}", sender.to_base58());
+            return;
+        }
+        for payload in replies {
+            match Packet::new_message(Some(sender), MsgType::RequestedBlock, rnd, &payload) {
+                None => {
+                    error!("failed to create requested block reply");
+                }
+                Some(pack) => {
+                    if let Err(e) = self.tx_send.send(pack) {
+                        warn!("failed send requested blocks: {}", e);
+                        return;
+                    }
+                }
+            }
+        }
+        debug!("transfer requested blocks of packet {} to {}", packet, sender.to_base58());
+    }
+
+    fn handle_requested_blocks(&mut self, sender: &PublicKey, _rnd: u64, bytes: Option<&[u8]>) {
+        let (blocks, packet) = match bytes.and_then(parse_blocks) {
+            None => {
+                warn!("malformed requested blocks from {}", sender.to_base58());
+                return;
+            }
+            Some(v) => v
+        };
+        let mut pools = Vec::<(Pool, Hash)>::with_capacity(blocks.len());
+        for b in blocks {
+            match Pool::from_bytes(b) {
+                Err(e) => {
+                    warn!("failed to unpack requested block from {}: {}", sender.to_base58(), e);
+                }
+                Ok(v) => pools.push(v)
+            }
+        }
+        debug!("{} requested blocks from {}, packet {}", pools.len(), sender.to_base58(), packet);
+        pools.sort_by_key(|(pool, _)| pool.sequence);
+        for (pool, hash) in pools {
+            if !self.pool_sync.is_requested(pool.sequence) {
+                debug!("block {} from {} is not requested, drop", pool.sequence, sender.to_base58());
+                continue;
+            }
+            self.pool_sync.on_received(pool.sequence);
+            self.accept_block(sender, pool, hash);
+        }
+    }
+
+    fn handle_new_block(&mut self, sender: &PublicKey, rnd: u64, bytes: Option<&[u8]>) {
+        let (pool, hash) = match bytes.map(Pool::from_bytes) {
+            Some(Ok(v)) => v,
+            Some(Err(e)) => {
+                warn!("failed to unpack new block from {}: {}", sender.to_base58(), e);
+                return;
+            }
+            None => {
+                warn!("malformed new block: no payload");
+                return;
+            }
+        };
+        let table_confidants = match self.round.table() {
+            None => {
+                debug!("no round table to verify new block {} of R {}", pool.sequence, rnd);
+                return;
+            }
+            Some(t) => t.confidants.clone()
+        };
+        // the block is signed by the majority of the round trusted nodes
+        if pool.confidants != table_confidants {
+            warn!("new block {} from {} is not built by trusted nodes of R {}", pool.sequence, sender.to_base58(), rnd);
+            return;
+        }
+        debug!("new block {} {} from {}", pool.sequence, hash, sender.to_base58());
+        self.accept_block(sender, pool, hash);
+    }
+
+    fn handle_block_hash(&mut self, sender: &PublicKey, rnd: u64, bytes: Option<&[u8]>) {
+        let (sequence, hash) = match bytes.and_then(parse_block_hash) {
+            Some(v) => v,
+            None => {
+                warn!("malformed block hash from {}", sender.to_base58());
+                return;
+            }
+        };
+        let own = self.storage.read().unwrap().hash_of(sequence);
+        match own {
+            None => {
+                debug!("block hash {} of R {}: no block {} yet", hash, rnd, sequence);
+            }
+            Some(h) if h == hash => {
+                debug!("block hash of {} is confirmed by {}", sequence, sender.to_base58());
+            }
+            Some(h) => {
+                self.on_block_mismatch(sender, sequence, &h, &hash, false);
+            }
+        }
+    }
+
+    /// the network has another block than ours, it is rolled back when the block is proven by trusted nodes
+    /// of a known round or the majority of the current round trusted nodes has it, other nodes only raise suspicion
+    fn on_block_mismatch(&mut self, sender: &PublicKey, sequence: u64, own: &Hash, other: &Hash, proven: bool) {
+        warn!("possible fork: our block {} is {}, {} has {}", sequence, own, sender.to_base58(), other);
+        let rnd = self.round.current();
+        let reason = if proven {
+            format!("{} has block {} {} proven by trusted nodes", sender.to_base58(), sequence, other)
+        }
+        else {
+            let trusted = match self.round.table() {
+                Some(t) if t.confidants.contains(sender) => Some(t.confidants.len()),
+                _ => None
+            };
+            match trusted {
+                Some(total) => {
+                    let count = self.rollback.confirm(rnd, sequence, sender);
+                    if count * 2 <= total {
+                        warn!("{} of {} trusted nodes disagree with our block {} in R {}", count, total, sequence, rnd);
+                        return;
+                    }
+                    format!("{} of {} trusted nodes of R {} have block {} {}, the last is {}", count, total, rnd, sequence, other, sender.to_base58())
+                }
+                None => {
+                    let count = self.rollback.suspect(rnd, sequence, sender);
+                    if count == FORK_CONFIRMATIONS {
+                        warn!("{} not trusted nodes disagree with our block {} in R {}", count, sequence, rnd);
+                        self.bus.publish(Event::ForkSuspected(sequence, *own));
+                    }
+                    return;
+                }
+            }
+        };
+        self.on_fork(sequence, own, &reason);
+    }
+
+    /// our block of sequence is not in chain, rolls it back with the following ones
+    fn on_fork(&mut self, sequence: u64, own: &Hash, reason: &str) {
+        error!("fork is detected: our block {} {} is not in chain", sequence, own);
+        if self.config.read().unwrap().events.report_invalid_block() {
+            self.bus.publish(Event::InvalidBlock(sequence, *own));
+        }
+        self.rollback_from(sequence, reason);
+    }
+
+    fn handle_hash_reply(&mut self, sender: &PublicKey, rnd: u64, bytes: Option<&[u8]>) {
+        let (sequence, hash) = match bytes.and_then(parse_block_hash) {
+            Some(v) => v,
+            None => {
+                warn!("malformed hash reply from {}", sender.to_base58());
+                return;
+            }
+        };
+        // trusted nodes reply, normal ones check their blocks
+        if self.role.role() != Role::Normal {
+            return;
+        }
+        let trusted = match self.round.table() {
+            Some(t) if t.confidants.contains(sender) => t.confidants.len(),
+            _ => {
+                debug!("hash reply from {} which is not trusted in R {}", sender.to_base58(), rnd);
+                return;
+            }
+        };
+        let own = match self.storage.read().unwrap().hash_of(sequence) {
+            None => {
+                debug!("hash reply of R {}: no block {} yet", rnd, sequence);
+                return;
+            }
+            Some(h) => h
+        };
+        if own != hash {
+            warn!("{} replies block {} is {}, ours is {}", sender.to_base58(), sequence, hash, own);
+        }
+        self.hash_replies.on_reply(rnd, trusted, own == hash, sender, sequence, &hash);
+    }
+
+    /// flags the fork if the majority of trusted nodes disagree with our block for several rounds
+    fn check_hash_replies(&mut self) {
+        let mismatch = match self.hash_replies.end_round() {
+            None => return,
+            Some(m) => m
+        };
+        let own = match self.storage.read().unwrap().hash_of(mismatch.sequence) {
+            None => return,
+            Some(h) => h
+        };
+        warn!("trusted nodes persistently reply another block {} {}, likely fork", mismatch.sequence, mismatch.hash);
+        self.bus.publish(Event::ForkSuspected(mismatch.sequence, own));
+        let reason = format!("trusted nodes reply block {} {}, the last from {}", mismatch.sequence, mismatch.hash, mismatch.sender.to_base58());
+        self.on_fork(mismatch.sequence, &own, &reason);
+    }
+
+    /// sends hash of our last block to the round
+    fn send_hash_reply(&mut self, rnd: u64) {
+        let (sequence, hash) = {
+            let storage = self.storage.read().unwrap();
+            match (storage.last_sequence(), storage.last_hash()) {
+                (Some(s), Some(h)) => (s, h),
+                _ => return
+            }
+        };
+        match Packet::new_message(None, MsgType::HashReply, rnd, &pack_block_hash(sequence, &hash)) {
+            None => {
+                error!("failed to create hash reply");
+            }
+            Some(pack) => {
+                match self.tx_send.send(pack) {
+                    Err(e) => {
+                        warn!("failed send hash reply: {}", e);
+                    }
+                    Ok(_) => {
+                        debug!("reply hash of block {} to R {}", sequence, rnd);
+                    }
+                }
+            }
+        }
+    }
+
+    /// removes blocks from sequence up to the last one and undoes their wallet changes
+    fn rollback_from(&mut self, sequence: u64, reason: &str) {
+        let mut storage = self.storage.write().unwrap();
+        let (last, top) = match (storage.last_sequence(), storage.last_hash()) {
+            (Some(s), Some(h)) if s >= sequence => (s, h),
+            _ => return
+        };
+        match self.rollback.first_undoable() {
+            Some(first) if first <= sequence => (),
+            first => {
+                error!("cannot roll back blocks from {}, the first undoable is {:?}", sequence, first);
+                return;
+            }
+        }
+        if let Err(e) = storage.truncate_from(sequence) {
+            error!("failed to roll back blocks from {}: {}", sequence, e);
+            return;
+        }
+        drop(storage);
+        while let Some(undo) = self.rollback.take_last(sequence) {
+            self.wallets.undo(undo);
+        }
+        self.rollback.clear_from(sequence);
+        warn!("blocks {}..{} are rolled back: {}", sequence, last, reason);
+
+        let mut file_name = PathBuf::from(&self.config.read().unwrap().data_dir);
+        file_name.push(ROLLBACK_LOG_FILE);
+        if let Err(e) = write_audit(&file_name, sequence, last, &top, reason) {
+            warn!("failed to log rollback into {}: {}", file_name.display(), e);
+        }
+    }
+
+    /// stores the next block and then cached ones following it, caches the block ahead of the next one
+    fn accept_block(&mut self, sender: &PublicKey, pool: Pool, hash: Hash) {
+        let (next, last_hash, stored) = {
+            let storage = self.storage.read().unwrap();
+            (storage.last_sequence().map(|s| s + 1).unwrap_or(0), storage.last_hash(), storage.sequence_of(&hash).is_some())
+        };
+        if pool.sequence < next {
+            if stored {
+                debug!("block {} is already stored", pool.sequence);
+            }
+            else {
+                debug!("block {} {} differs from the stored one", pool.sequence, hash);
+            }
+            return;
+        }
+        // cached blocks are verified as well
+        if let Err(e) = verify_signatures(&pool, &hash) {
+            warn!("block {} from {} is rejected: {}", pool.sequence, sender.to_base58(), e);
+            return;
+        }
+        if pool.sequence > next {
+            if self.pending.insert(pool, hash) {
+                debug!("block is cached until {} is stored, {} cached", next, self.pending.len());
+            }
+            return;
+        }
+        if let Some(h) = last_hash {
+            if pool.previous_hash != h {
+                // the sender has another block before this one, it is proven by trusted nodes of a known round only
+                let known = self.round.is_known_confidants(&pool.confidants);
+                self.on_block_mismatch(sender, next - 1, &h, &pool.previous_hash, known);
+                return;
+            }
+        }
+        let mut item = Some(pool);
+        while let Some(pool) = item.take() {
+            let seq = pool.sequence;
+            if !self.store_block(pool) {
+                break;
+            }
+            self.pending.drop_stored(seq);
+            item = self.pending.take(seq + 1).map(|(p, _)| p);
+        }
+    }
+
+    /// stores the verified block if it follows our last block
+    fn store_block(&mut self, pool: Pool) -> bool {
+        let mut storage = self.storage.write().unwrap();
+        if let Some(h) = storage.last_hash() {
+            if pool.previous_hash != h {
+                warn!("block {} does not refer our last block {}", pool.sequence, h);
+                return false;
+            }
+        }
+        match storage.store(&pool) {
+            Err(e) => {
+                error!("failed to store block {}: {}", pool.sequence, e);
+                false
+            }
+            Ok(_) => {
+                self.pool_sync.on_stored(pool.sequence);
+                self.rollback.on_applied(self.wallets.apply(&pool));
+                true
+            }
+        }
+    }
+
+    fn on_sync_state_changed(&mut self, s: SyncState) {
+        info!("sync state is {}", s);
+        {
+            let mut guard = self.state.write().unwrap();
+            guard.sync = s;
+        }
+        self.bus.publish(Event::SyncStateChanged(s));
+        if let Some(r) = self.role.set_synced(s == SyncState::Synced) {
+            self.on_role_changed(r);
+        }
+    }
+
+    fn on_role_changed(&self, r: Role) {
+        info!("node role is {}", r);
+        {
+            let mut guard = self.state.write().unwrap();
+            guard.role = r;
+        }
+        self.bus.publish(Event::RoleChanged(r));
+    }
+
+    fn handle_stop_request(&self, sender: &PublicKey, _rnd: u64, bytes: Option<&[u8]>) {
+        /*
+            uint16_t version = 0;
+            istream_ >> version;
+        */
+        let input = match bytes {
+            Some(v) if v.len() >= 2 => v,
+            _ => {
+                warn!("malformed stop request: no required version");
+                return;
+            }
+        };
+        let authorized;
+        {
+            let conf_guard = self.config.read().unwrap();
+            authorized = conf_guard.stop_request_keys
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|k| !k.is_empty())
+                .any(|k| &parse_public_key(k) == sender);
+        }
+        if !authorized {
+            warn!("stop request from not authorized {}, ignore", sender.to_base58());
+            return;
+        }
+        let version = u16::from_le_bytes([input[0], input[1]]);
+        if NODE_VERSION >= version {
+            info!("stop request for versions below {} is received, our version {} is actual", version, NODE_VERSION);
+            return;
+        }
+        error!("NODE VERSION {} IS OUTDATED, {} IS REQUIRED BY {}. STOP THE NODE, UPDATE IT PLEASE", NODE_VERSION, version, sender.to_base58());
+        self.bus.publish(Event::StopRequested(version));
+    }
+}
+
+/// wallet state of the stored blocks and undo of the last MAX_ROLLBACK_BLOCKS ones
+fn load_wallets(storage: &Storage) -> (Wallets, Rollback) {
+    let mut wallets = Wallets::new();
+    let mut rollback = Rollback::new();
+    let (first, last) = match (storage.first_sequence(), storage.last_sequence()) {
+        (Some(f), Some(l)) => (f, l),
+        _ => return (wallets, rollback)
+    };
+    let undoable = (last + 1).saturating_sub(MAX_ROLLBACK_BLOCKS as u64);
+    for seq in first..=last {
+        match storage.get(seq) {
+            Ok(Some(pool)) => {
+                let undo = wallets.apply(&pool);
+                if seq >= undoable {
+                    rollback.on_applied(undo);
+                }
+            }
+            Ok(None) => (),
+            Err(e) => {
+                error!("failed to read block {} to load wallets: {}", seq, e);
+                break;
+            }
+        }
+    }
+    info!("wallets are loaded from blocks {}..{}", first, last);
+    (wallets, rollback)
+}
+
+/// encodes payload of BlockHash and HashReply, the layout parse_block_hash() reads
+fn pack_block_hash(sequence: u64, hash: &Hash) -> Vec<u8> {
+    let mut output = Vec::<u8>::with_capacity(8 + 8 + HASH_SIZE);
+    output.extend_from_slice(&sequence.to_le_bytes());
+    output.extend_from_slice(&(HASH_SIZE as u64).to_le_bytes());
+    output.extend_from_slice(hash.as_bytes());
+    output
+}
+
+/// decodes payload of BlockHash and HashReply: sequence and hash of the block
+fn parse_block_hash(input: &[u8]) -> Option<(u64, Hash)> {
+    /*
+        cs::Sequence sequence = 0;
+        csdb::PoolHash hash;
+        stream >> sequence >> hash;
+
+        PoolHash is streamed as its binary: size(8) + bytes
+    */
+    if input.len() != 8 + 8 + HASH_SIZE {
+        return None;
+    }
+    if u64::from_le_bytes(input[8..16].try_into().unwrap()) != HASH_SIZE as u64 {
+        return None;
+    }
+    Some((u64::from_le_bytes(input[..8].try_into().unwrap()), Hash::from_slice(&input[16..])?))
+}
+
+/// decodes payload of TransactionsPacketRequest
+fn parse_packet_hashes(input: &[u8]) -> Result<Vec<PacketHash>, String> {
+    /*
+        std::size_t hashesCount = 0;
+        stream >> hashesCount;
+        for (std::size_t i = 0; i < hashesCount; ++i) {
+            cs::TransactionsPacketHash hash;
+            stream >> hash;
+            hashes.push_back(std::move(hash));
+        }
+    */
+    if input.len() < 8 {
+        return Err("no hashes count".to_string());
+    }
+    let count = u64::from_le_bytes(input[..8].try_into().unwrap());
+    let rest = input.len() - 8;
+    if rest % HASH_SIZE != 0 || (rest / HASH_SIZE) as u64 != count {
+        return Err(format!("{} hashes do not fit {} bytes", count, input.len()));
+    }
+    Ok(input[8..].chunks(HASH_SIZE).filter_map(Hash::from_slice).collect())
+}
+
+fn parse_public_key(key: &str) -> PublicKey {
+    match key.from_base58() {
+        Ok(ref bytes) if bytes.len() == PUBLIC_KEY_SIZE => bytes[..].try_into().unwrap(),
+        _ => {
+            warn!("public key must be a 32-byte key encoded base58, found {}", key);
+            [0u8; PUBLIC_KEY_SIZE]
+        }
+    }
+}
+
+#[test]
+fn test_block_hash() {
+    let mut input = vec![7u8, 1, 0, 0, 0, 0, 0, 0];
+    input.extend_from_slice(&[32u8, 0, 0, 0, 0, 0, 0, 0]);
+    input.extend_from_slice(&[5u8; HASH_SIZE]);
+    assert_eq!(parse_block_hash(&input), Some((0x0107, Hash([5u8; HASH_SIZE]))));
+    assert_eq!(pack_block_hash(0x0107, &Hash([5u8; HASH_SIZE])), input);
+    assert_eq!(parse_block_hash(&input[..input.len() - 1]), None);
+    input[8] = 31;
+    assert_eq!(parse_block_hash(&input), None);
+}
+
+#[test]
+fn test_parse_packet_hashes() {
+    // hashesCount as std::size_t, then hashes
+    let mut input = vec![2u8, 0, 0, 0, 0, 0, 0, 0];
+    input.extend_from_slice(&[3u8; HASH_SIZE]);
+    input.extend_from_slice(&[4u8; HASH_SIZE]);
+    assert_eq!(parse_packet_hashes(&input), Ok(vec![Hash([3u8; HASH_SIZE]), Hash([4u8; HASH_SIZE])]));
+    assert!(parse_packet_hashes(&input[..input.len() - 1]).is_err());
+    input[0] = 3;
+    assert!(parse_packet_hashes(&input).is_err());
+    assert!(parse_packet_hashes(&[0u8; 4]).is_err());
 }
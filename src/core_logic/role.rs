@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+
+use log::debug;
+
+use super::super::PublicKey;
+use super::super::node_state::Role;
+use super::super::network::packet::MsgType;
+use super::round_table::RoundTable;
+
+/// Works out the role of the node from the round table, our public key and the writer
+/// the trusted nodes agree on in their third stages
+pub struct RoleMachine {
+    own_key: PublicKey,
+    role: Role,
+    /// not synced node always acts as normal one
+    synced: bool,
+    /// third stages of the current round: confidant index -> writer index
+    writer_votes: HashMap<u8, u8>
+}
+
+impl RoleMachine {
+
+    pub fn new(own_key: PublicKey) -> RoleMachine {
+        RoleMachine {
+            own_key: own_key,
+            role: Role::Normal,
+            synced: true,
+            writer_votes: HashMap::new()
+        }
+    }
+
+    pub fn role(&self) -> Role {
+        self.role
+    }
+
+    /// sync state is changed, returns new role if it is changed
+    pub fn set_synced(&mut self, synced: bool) -> Option<Role> {
+        self.synced = synced;
+        if !synced && self.role != Role::Normal {
+            debug!("role: {} -> {}, not synced", self.role, Role::Normal);
+            self.role = Role::Normal;
+            return Some(Role::Normal);
+        }
+        None
+    }
+
+    /// new round table is received, returns new role if it is changed
+    pub fn update(&mut self, table: &RoundTable) -> Option<Role> {
+        self.writer_votes.clear();
+        let next = if self.synced && table.confidant_index(&self.own_key).is_some() {
+            Role::Trusted
+        }
+        else {
+            Role::Normal
+        };
+        if next == self.role {
+            return None;
+        }
+        debug!("role: {} -> {}", self.role, next);
+        self.role = next;
+        Some(next)
+    }
+
+    /// third stage of the confidant names the writer, we become the writer when the most
+    /// of confidants name us; returns new role if it is changed
+    pub fn on_stage_three(&mut self, table: &RoundTable, sender: u8, writer: u8) -> Option<Role> {
+        if self.role != Role::Trusted || writer as usize >= table.confidants.len() {
+            return None;
+        }
+        self.writer_votes.insert(sender, writer);
+        let votes = self.writer_votes.values().filter(|w| **w == writer).count();
+        if votes <= table.confidants.len() / 2 || table.confidant_index(&self.own_key) != Some(writer as usize) {
+            return None;
+        }
+        debug!("role: {} -> {}", self.role, Role::Writer);
+        self.role = Role::Writer;
+        Some(Role::Writer)
+    }
+
+    /// test the message is handled in the current role
+    pub fn is_active(&self, msg: &MsgType) -> bool {
+        match self.role {
+            Role::Normal => !is_trusted_msg(msg) && !is_writer_msg(msg),
+            Role::Trusted => !is_writer_msg(msg),
+            Role::Writer => true
+        }
+    }
+}
+
+/// messages only the writer handles
+fn is_writer_msg(msg: &MsgType) -> bool {
+    match msg {
+        MsgType::WriterNotification => true,
+        _ => false
+    }
+}
+
+/// messages only trusted nodes handle
+fn is_trusted_msg(msg: &MsgType) -> bool {
+    match msg {
+        MsgType::FirstStage |
+        MsgType::SecondStage |
+        MsgType::ThirdStage |
+        MsgType::FirstStageRequest |
+        MsgType::SecondStageRequest |
+        MsgType::ThirdStageRequest |
+        MsgType::FirstSmartStage |
+        MsgType::SecondSmartStage |
+        MsgType::ThirdSmartStage |
+        MsgType::SmartFirstStageRequest |
+        MsgType::SmartSecondStageRequest |
+        MsgType::SmartThirdStageRequest |
+        MsgType::RejectedContracts => true,
+        _ => false
+    }
+}
+
+#[test]
+fn test_role_update() {
+    let own_key = [7u8; 32];
+    let mut roles = RoleMachine::new(own_key);
+    let mut table = RoundTable {
+        round: 10,
+        confidants: vec![[1u8; 32], own_key],
+        hashes: Vec::new()
+    };
+    assert_eq!(roles.update(&table), Some(Role::Trusted));
+    assert!(roles.is_active(&MsgType::FirstStage));
+    assert_eq!(roles.update(&table), None);
+
+    assert_eq!(roles.set_synced(false), Some(Role::Normal));
+    assert_eq!(roles.update(&table), None);
+    assert!(!roles.is_active(&MsgType::FirstStage));
+    assert!(roles.is_active(&MsgType::RoundTable));
+
+    roles.set_synced(true);
+    table.confidants.pop();
+    assert_eq!(roles.update(&table), None);
+}
+
+#[test]
+fn test_role_writer() {
+    let own_key = [7u8; 32];
+    let mut roles = RoleMachine::new(own_key);
+    let table = RoundTable {
+        round: 10,
+        confidants: vec![[1u8; 32], own_key, [2u8; 32]],
+        hashes: Vec::new()
+    };
+    assert_eq!(roles.update(&table), Some(Role::Trusted));
+    assert!(!roles.is_active(&MsgType::WriterNotification));
+    // the same confidant votes twice, then another one agrees
+    assert_eq!(roles.on_stage_three(&table, 0, 1), None);
+    assert_eq!(roles.on_stage_three(&table, 0, 1), None);
+    assert_eq!(roles.on_stage_three(&table, 2, 1), Some(Role::Writer));
+    assert!(roles.is_active(&MsgType::WriterNotification));
+
+    // the next round starts as trusted again
+    assert_eq!(roles.update(&table), Some(Role::Trusted));
+    assert_eq!(roles.on_stage_three(&table, 0, 2), None);
+    assert_eq!(roles.on_stage_three(&table, 1, 2), None);
+    assert_eq!(roles.role(), Role::Trusted);
+}
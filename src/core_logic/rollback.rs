@@ -0,0 +1,154 @@
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::SystemTime;
+
+use super::super::{Hash, PublicKey};
+use super::super::csdb::BlockUndo;
+
+/// max count of the last blocks able to roll back
+pub const MAX_ROLLBACK_BLOCKS: usize = 100;
+/// count of not trusted nodes disagreeing with our block in a round to suspect a fork
+pub const FORK_CONFIRMATIONS: usize = 2;
+
+/// Undo journal of the last stored blocks and suspicions of them being a fork
+pub struct Rollback {
+    journal: VecDeque<BlockUndo>,
+    /// the round suspicions are collected in
+    round: u64,
+    /// sequence -> not trusted nodes having another block
+    suspicions: BTreeMap<u64, HashSet<PublicKey>>,
+    /// sequence -> trusted nodes having another block
+    confirmations: BTreeMap<u64, HashSet<PublicKey>>
+}
+
+impl Rollback {
+
+    pub fn new() -> Rollback {
+        Rollback {
+            journal: VecDeque::new(),
+            round: 0,
+            suspicions: BTreeMap::new(),
+            confirmations: BTreeMap::new()
+        }
+    }
+
+    /// the block is stored and applied to wallets
+    pub fn on_applied(&mut self, undo: BlockUndo) {
+        self.journal.push_back(undo);
+        if self.journal.len() > MAX_ROLLBACK_BLOCKS {
+            self.journal.pop_front();
+        }
+    }
+
+    /// the first sequence blocks can be rolled back from
+    pub fn first_undoable(&self) -> Option<u64> {
+        self.journal.front().map(|u| u.sequence)
+    }
+
+    /// takes undo of the last applied block if it is not below sequence
+    pub fn take_last(&mut self, sequence: u64) -> Option<BlockUndo> {
+        match self.journal.back() {
+            Some(u) if u.sequence >= sequence => self.journal.pop_back(),
+            _ => None
+        }
+    }
+
+    /// the not trusted node has another block of sequence in round rnd, returns count of such nodes in the round;
+    /// suspicions of the previous rounds expire
+    pub fn suspect(&mut self, rnd: u64, sequence: u64, sender: &PublicKey) -> usize {
+        self.expire(rnd);
+        let senders = self.suspicions.entry(sequence).or_default();
+        senders.insert(*sender);
+        senders.len()
+    }
+
+    /// the trusted node of round rnd has another block of sequence, returns count of such nodes in the round
+    pub fn confirm(&mut self, rnd: u64, sequence: u64, sender: &PublicKey) -> usize {
+        self.expire(rnd);
+        let senders = self.confirmations.entry(sequence).or_default();
+        senders.insert(*sender);
+        senders.len()
+    }
+
+    /// forgets suspicions from sequence, e.g. the blocks are rolled back
+    pub fn clear_from(&mut self, sequence: u64) {
+        self.suspicions.split_off(&sequence);
+        self.confirmations.split_off(&sequence);
+    }
+
+    fn expire(&mut self, rnd: u64) {
+        if rnd != self.round {
+            self.round = rnd;
+            self.suspicions.clear();
+            self.confirmations.clear();
+        }
+    }
+}
+
+/// appends the rollback record to the audit log file
+pub fn write_audit(file_name: &Path, from: u64, to: u64, top: &Hash, reason: &str) -> io::Result<()> {
+    if let Some(dir) = file_name.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let mut file = OpenOptions::new().append(true).create(true).open(file_name)?;
+    writeln!(file, "{} rollback blocks {}..{} top {}: {}", time, from, to, top, reason)
+}
+
+#[test]
+fn test_rollback_journal() {
+    use super::super::csdb::{test_pool, Wallets};
+
+    let mut wallets = Wallets::new();
+    let mut rollback = Rollback::new();
+    assert_eq!(rollback.first_undoable(), None);
+    for seq in 0..MAX_ROLLBACK_BLOCKS as u64 + 5 {
+        rollback.on_applied(wallets.apply(&test_pool(Hash::default(), seq)));
+    }
+    assert_eq!(rollback.first_undoable(), Some(5));
+    let last = MAX_ROLLBACK_BLOCKS as u64 + 4;
+    assert_eq!(rollback.take_last(last).unwrap().sequence, last);
+    assert!(rollback.take_last(last).is_none());
+
+    assert_eq!(rollback.suspect(7, 10, &[1u8; 32]), 1);
+    assert_eq!(rollback.suspect(7, 10, &[1u8; 32]), 1);
+    assert_eq!(rollback.suspect(7, 10, &[2u8; 32]), FORK_CONFIRMATIONS);
+    rollback.clear_from(10);
+    assert_eq!(rollback.suspect(7, 10, &[1u8; 32]), 1);
+    assert_eq!(rollback.suspect(8, 10, &[2u8; 32]), 1);
+    // trusted nodes are counted apart
+    assert_eq!(rollback.confirm(8, 10, &[2u8; 32]), 1);
+    assert_eq!(rollback.confirm(8, 10, &[3u8; 32]), 2);
+    assert_eq!(rollback.confirm(9, 10, &[3u8; 32]), 1);
+
+    let dir = super::super::storage::TestDir::new("rollback");
+    let file_name = dir.path().join("rollback.log");
+    write_audit(&file_name, 3, 4, &Hash::default(), "test").unwrap();
+    write_audit(&file_name, 4, 4, &Hash::default(), "test").unwrap();
+    let text = fs::read_to_string(&file_name).unwrap();
+    assert_eq!(text.lines().count(), 2);
+    assert!(text.lines().next().unwrap().ends_with("rollback blocks 3..4 top 0000000000000000000000000000000000000000000000000000000000000000: test"));
+}
+
+#[test]
+fn test_rollback_journal_on_start() {
+    use super::super::csdb::test_pool;
+
+    let (mut storage, _dir) = super::super::storage::test_storage("rollback-start");
+    let mut prev = Hash::default();
+    for seq in 0..3 {
+        prev = storage.store(&test_pool(prev, seq)).unwrap();
+    }
+    // the undo journal is rebuilt from the last stored blocks
+    let (_, mut rollback) = super::load_wallets(&storage);
+    assert_eq!(rollback.first_undoable(), Some(0));
+    assert_eq!(rollback.take_last(2).unwrap().sequence, 2);
+    for seq in 3..MAX_ROLLBACK_BLOCKS as u64 + 5 {
+        prev = storage.store(&test_pool(prev, seq)).unwrap();
+    }
+    let (_, rollback) = super::load_wallets(&storage);
+    assert_eq!(rollback.first_undoable(), Some(5));
+    assert_eq!(rollback.journal.len(), MAX_ROLLBACK_BLOCKS);
+}
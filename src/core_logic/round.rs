@@ -1,8 +1,20 @@
 use std::time::Instant;
-use log::{info};
+use std::collections::BTreeMap;
+use log::{debug, info, warn};
 
 use num_format::{Locale, ToFormattedString};
 
+/// max count of the last round tables to keep to reply requests
+const MAX_CACHED_TABLES: usize = 16;
+
+use super::super::PublicKey;
+use super::round_table::RoundTable;
+use super::role::RoleMachine;
+use super::round_history::{RoundHistory, RoundRecord};
+
+extern crate base58;
+use base58::ToBase58;
+
 pub struct Round {
     // the first round after start
     first: u64,
@@ -13,7 +25,15 @@ pub struct Round {
     // time point current round started  
     current_start: Instant,
     // average round duration
-    ave_duration: u64
+    ave_duration: u64,
+    // decoded table of current round
+    table: Option<RoundTable>,
+    // the last rounds
+    history: RoundHistory,
+    // payloads of the last round tables as received, bootstrap tables are not cached
+    cached_tables: BTreeMap<u64, Vec<u8>>,
+    // trusted nodes of the last rounds
+    cached_confidants: BTreeMap<u64, Vec<PublicKey>>
 }
 
 impl Round {
@@ -25,7 +45,11 @@ impl Round {
             first_start: now,
             current: 0,
             current_start: now,
-            ave_duration: 0
+            ave_duration: 0,
+            table: None,
+            history: RoundHistory::new(),
+            cached_tables: BTreeMap::<u64, Vec<u8>>::new(),
+            cached_confidants: BTreeMap::<u64, Vec<PublicKey>>::new()
         }
     }
 
@@ -33,7 +57,86 @@ impl Round {
         self.current
     }
 
-    pub fn handle_table(&mut self, rnd: u64, _bytes: Option<&[u8]>) -> bool {
+    pub fn table(&self) -> Option<&RoundTable> {
+        self.table.as_ref()
+    }
+
+    /// payload of the cached round table of rnd
+    pub fn cached_table(&self, rnd: u64) -> Option<&[u8]> {
+        self.cached_tables.get(&rnd).map(|v| &v[..])
+    }
+
+    /// keys are trusted nodes of one of the last rounds
+    pub fn is_known_confidants(&self, keys: &[PublicKey]) -> bool {
+        self.cached_confidants.values().any(|c| &c[..] == keys)
+    }
+
+    pub fn history(&mut self) -> &mut RoundHistory {
+        &mut self.history
+    }
+
+    /// no new round during factor * average round duration
+    pub fn is_stalled(&self, factor: u32) -> bool {
+        if factor == 0 || self.current == 0 {
+            return false;
+        }
+        let ave = self.history.average().unwrap_or(self.ave_duration);
+        if ave == 0 {
+            return false;
+        }
+        self.current_start.elapsed().as_millis() as u64 > ave * factor as u64
+    }
+
+    /// no new round during factor * average round duration since the time point
+    pub fn is_stalled_since(&self, since: Instant, factor: u32) -> bool {
+        let ave = self.history.average().unwrap_or(self.ave_duration);
+        since.elapsed().as_millis() as u64 > ave * factor as u64
+    }
+
+    /// time since the current round started in ms
+    pub fn elapsed(&self) -> u64 {
+        self.current_start.elapsed().as_millis() as u64
+    }
+
+    pub fn handle_table(&mut self, sender: &PublicKey, rnd: u64, bytes: Option<&[u8]>, roles: &mut RoleMachine) -> bool {
+        let input = match bytes {
+            None => {
+                warn!("malformed round table: no payload");
+                return false;
+            }
+            Some(v) => v
+        };
+        let table = match RoundTable::from_bytes(rnd, input) {
+            Err(e) => {
+                warn!("failed to unpack round table: {}", e);
+                return false;
+            }
+            Ok(t) => t
+        };
+        self.start(sender, table, Some(input), roles)
+    }
+
+    /// starts the round of the table, payload is the table as received to reply requests of it;
+    /// returns false if the round is started already
+    pub fn start(&mut self, sender: &PublicKey, table: RoundTable, payload: Option<&[u8]>, roles: &mut RoleMachine) -> bool {
+        let rnd = table.round;
+        if self.table.is_some() && rnd == self.current {
+            debug!("round table of R {} from {} is handled already", rnd, sender.to_base58());
+            return false;
+        }
+        if let Some(bytes) = payload {
+            self.cached_tables.insert(rnd, bytes.to_vec());
+            while self.cached_tables.len() > MAX_CACHED_TABLES {
+                let oldest = *self.cached_tables.keys().next().unwrap();
+                self.cached_tables.remove(&oldest);
+            }
+        }
+        self.cached_confidants.insert(rnd, table.confidants.clone());
+        while self.cached_confidants.len() > MAX_CACHED_TABLES {
+            let oldest = *self.cached_confidants.keys().next().unwrap();
+            self.cached_confidants.remove(&oldest);
+        }
+
         if self.first == 0 {
             self.first = rnd;
         }
@@ -45,10 +148,24 @@ impl Round {
             self.ave_duration = uptime_ms / (self.current - self.first);
         }
 
+        self.history.push(RoundRecord {
+            round: rnd,
+            start: self.current_start,
+            duration: None,
+            trusted: table.confidants.len(),
+            transactions: 0,
+            initiator: *sender
+        });
+
+        roles.update(&table);
 
-        
-        info!("-------------------------- R: {} --------------------------", rnd.to_formatted_string(&Locale::ru));
+        info!("-------------------------- R: {}, {} --------------------------", rnd.to_formatted_string(&Locale::ru), roles.role());
         info!("last round: {} ms, ave: {} ms, uptime: {}", current_duration.as_millis(), self.ave_duration, format_ms(uptime_ms));
+        info!("trusted: {}, packets: {}", table.confidants.len(), table.hashes.len());
+        if let Some((p50, p95, p99)) = self.history.percentiles() {
+            info!("round duration p50: {} ms, p95: {} ms, p99: {} ms", p50, p95, p99);
+        }
+        self.table = Some(table);
         true
     }
 }
@@ -81,3 +198,32 @@ fn test_format_ms() {
     assert_eq!(ss, 29);
     assert_eq!(ms, 345);
 }
+
+#[test]
+fn test_cached_table() {
+    let mut round = Round::new();
+    let mut roles = RoleMachine::new([7u8; 32]);
+    let table = |rnd: u64| RoundTable {
+        round: rnd,
+        confidants: vec![[1u8; 32]],
+        hashes: Vec::new()
+    };
+    for rnd in 10..12 {
+        // unknown trailing bytes are replied as received
+        let mut payload = table(rnd).to_bytes();
+        payload.push(rnd as u8);
+        assert!(round.start(&[1u8; 32], table(rnd), Some(&payload), &mut roles));
+    }
+    assert_eq!(round.cached_table(10), Some(&[&table(10).to_bytes()[..], &[10u8]].concat()[..]));
+    assert_eq!(round.cached_table(11), Some(&[&table(11).to_bytes()[..], &[11u8]].concat()[..]));
+    assert!(round.cached_table(9).is_none());
+    assert!(round.cached_table(12).is_none());
+
+    // the second table of the current round is ignored, bootstrap one is not cached
+    assert!(!round.start(&[2u8; 32], table(11), None, &mut roles));
+    assert_eq!(round.history().last().unwrap().initiator, [1u8; 32]);
+    assert!(round.start(&[2u8; 32], table(12), None, &mut roles));
+    assert!(round.cached_table(12).is_none());
+    assert!(round.is_known_confidants(&[[1u8; 32]]));
+    assert!(!round.is_known_confidants(&[[1u8; 32], [2u8; 32]]));
+}
@@ -0,0 +1,105 @@
+use std::collections::VecDeque;
+use std::time::Instant;
+
+use super::super::PublicKey;
+
+/// max count of rounds to keep in history
+const MAX_HISTORY_SIZE: usize = 1000;
+
+pub struct RoundRecord {
+    pub round: u64,
+    /// time point the round started
+    pub start: Instant,
+    /// round duration in ms, None until the next round starts
+    pub duration: Option<u64>,
+    /// count of trusted nodes
+    pub trusted: usize,
+    /// count of transactions in round
+    pub transactions: usize,
+    /// sender of the round table
+    pub initiator: PublicKey
+}
+
+/// Bounded history of the last rounds
+pub struct RoundHistory {
+    records: VecDeque<RoundRecord>
+}
+
+impl RoundHistory {
+
+    pub fn new() -> RoundHistory {
+        RoundHistory {
+            records: VecDeque::<RoundRecord>::with_capacity(MAX_HISTORY_SIZE)
+        }
+    }
+
+    /// completes the last round and starts the new one
+    pub fn push(&mut self, record: RoundRecord) {
+        if let Some(last) = self.records.back_mut() {
+            if last.duration.is_none() {
+                last.duration = Some(record.start.duration_since(last.start).as_millis() as u64);
+            }
+        }
+        if self.records.len() >= MAX_HISTORY_SIZE {
+            self.records.pop_front();
+        }
+        self.records.push_back(record);
+    }
+
+    /// the current round
+    pub fn last(&self) -> Option<&RoundRecord> {
+        self.records.back()
+    }
+
+    pub fn set_transactions(&mut self, round: u64, count: usize) {
+        if let Some(r) = self.records.iter_mut().rev().find(|r| r.round == round) {
+            r.transactions = count;
+        }
+    }
+
+    /// p50, p95 and p99 of completed round durations in ms
+    pub fn percentiles(&self) -> Option<(u64, u64, u64)> {
+        let mut durations: Vec<u64> = self.records.iter().filter_map(|r| r.duration).collect();
+        if durations.is_empty() {
+            return None;
+        }
+        durations.sort();
+        Some((percentile(&durations, 50), percentile(&durations, 95), percentile(&durations, 99)))
+    }
+
+    /// average of completed round durations in ms
+    pub fn average(&self) -> Option<u64> {
+        let durations: Vec<u64> = self.records.iter().filter_map(|r| r.duration).collect();
+        if durations.is_empty() {
+            return None;
+        }
+        Some(durations.iter().sum::<u64>() / durations.len() as u64)
+    }
+}
+
+/// nearest-rank percentile of sorted values
+fn percentile(sorted: &[u64], p: usize) -> u64 {
+    let rank = (p * sorted.len() + 99) / 100;
+    sorted[std::cmp::max(rank, 1) - 1]
+}
+
+#[test]
+fn test_percentiles() {
+    let mut history = RoundHistory::new();
+    assert_eq!(history.percentiles(), None);
+    let start = Instant::now();
+    for i in 0..101 {
+        history.push(RoundRecord {
+            round: i,
+            start: start + std::time::Duration::from_millis(i * (i + 1) / 2),
+            duration: None,
+            trusted: 5,
+            transactions: 0,
+            initiator: [0u8; 32]
+        });
+    }
+    // durations are 1, 2, .., 100 ms, the last round is not completed
+    assert_eq!(history.percentiles(), Some((50, 95, 99)));
+    assert_eq!(history.average(), Some(50));
+    assert!(history.last().unwrap().duration.is_none());
+}
@@ -0,0 +1,154 @@
+use std::mem::size_of_val;
+
+use super::super::{PublicKey, HASH_SIZE, PUBLIC_KEY_SIZE};
+use super::super::csdb::Hash;
+
+extern crate bincode;
+use bincode::deserialize_from;
+
+pub type PacketHash = Hash;
+
+/// Round table: trusted nodes of the round and transaction packets to build the block from
+pub struct RoundTable {
+    pub round: u64,
+    /// trusted nodes
+    pub confidants: Vec<PublicKey>,
+    /// hashes of transaction packets of the round
+    pub hashes: Vec<PacketHash>
+}
+
+impl RoundTable {
+
+    /// decodes payload of RoundTable message, round number is taken from message header
+    pub fn from_bytes(round: u64, input: &[u8]) -> bincode::Result<RoundTable> {
+        /*
+            cs::Byte confidantsCount = 0;
+            istream_ >> confidantsCount;
+            cs::Byte hashesCount = 0;
+            istream_ >> hashesCount;
+            for (size_t i = 0; i < confidantsCount; ++i) {
+                cs::PublicKey key;
+                istream_ >> key;
+                confidants.push_back(std::move(key));
+            }
+            for (size_t i = 0; i < hashesCount; ++i) {
+                cs::TransactionsPacketHash hash;
+                istream_ >> hash;
+                hashes.push_back(hash);
+            }
+        */
+        let confidants_count: u8 = deserialize_from(input)?;
+        let mut p = size_of_val(&confidants_count);
+        let hashes_count: u8 = deserialize_from(&input[p..])?;
+        p += size_of_val(&hashes_count);
+
+        let req_len = p + confidants_count as usize * PUBLIC_KEY_SIZE + hashes_count as usize * HASH_SIZE;
+        if input.len() < req_len {
+            return Err(Box::new(bincode::ErrorKind::Custom(
+                format!("inconsistent round table payload, required {} bytes, actual {}", req_len, input.len()))));
+        }
+
+        let mut confidants = Vec::<PublicKey>::with_capacity(confidants_count as usize);
+        for _ in 0..confidants_count {
+            let key: PublicKey = deserialize_from(&input[p..])?;
+            p += size_of_val(&key);
+            confidants.push(key);
+        }
+        let mut hashes = Vec::<PacketHash>::with_capacity(hashes_count as usize);
+        for _ in 0..hashes_count {
+            let hash: [u8; HASH_SIZE] = deserialize_from(&input[p..])?;
+            p += size_of_val(&hash);
+            hashes.push(Hash(hash));
+        }
+
+        Ok(RoundTable {
+            round: round,
+            confidants: confidants,
+            hashes: hashes
+        })
+    }
+
+    /// decodes payload of BootstrapTable message, round number is taken from message header
+    pub fn from_bootstrap_bytes(round: u64, input: &[u8]) -> bincode::Result<RoundTable> {
+        /*
+            cs::Byte confSize = 0;
+            istream_ >> confSize;
+            for (size_t i = 0; i < confSize; ++i) {
+                cs::PublicKey key;
+                istream_ >> key;
+                confidants.push_back(key);
+            }
+        */
+        let confidants_count: u8 = deserialize_from(input)?;
+        let mut p = size_of_val(&confidants_count);
+
+        let req_len = p + confidants_count as usize * PUBLIC_KEY_SIZE;
+        if input.len() < req_len {
+            return Err(Box::new(bincode::ErrorKind::Custom(
+                format!("inconsistent bootstrap table payload, required {} bytes, actual {}", req_len, input.len()))));
+        }
+
+        let mut confidants = Vec::<PublicKey>::with_capacity(confidants_count as usize);
+        for _ in 0..confidants_count {
+            let key: PublicKey = deserialize_from(&input[p..])?;
+            p += size_of_val(&key);
+            confidants.push(key);
+        }
+
+        Ok(RoundTable {
+            round: round,
+            confidants: confidants,
+            hashes: Vec::new()
+        })
+    }
+
+    /// encodes as payload of RoundTable message
+    #[cfg(test)]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut output = Vec::<u8>::with_capacity(2 + self.confidants.len() * PUBLIC_KEY_SIZE + self.hashes.len() * HASH_SIZE);
+        output.push(self.confidants.len() as u8);
+        output.push(self.hashes.len() as u8);
+        for key in &self.confidants {
+            output.extend_from_slice(key);
+        }
+        for hash in &self.hashes {
+            output.extend_from_slice(hash.as_bytes());
+        }
+        output
+    }
+
+    /// index of key in confidants if any
+    pub fn confidant_index(&self, key: &PublicKey) -> Option<usize> {
+        self.confidants.iter().position(|k| k == key)
+    }
+}
+
+#[test]
+fn test_round_table_from_bytes() {
+    let mut input = vec![2u8, 1u8];
+    input.extend_from_slice(&[1u8; PUBLIC_KEY_SIZE]);
+    input.extend_from_slice(&[2u8; PUBLIC_KEY_SIZE]);
+    input.extend_from_slice(&[3u8; HASH_SIZE]);
+
+    let table = RoundTable::from_bytes(1_000, &input).unwrap();
+    assert_eq!(table.round, 1_000);
+    assert_eq!(table.confidants.len(), 2);
+    assert_eq!(table.confidant_index(&[2u8; PUBLIC_KEY_SIZE]), Some(1));
+    assert_eq!(table.confidant_index(&[3u8; PUBLIC_KEY_SIZE]), None);
+    assert_eq!(table.hashes, vec![Hash([3u8; HASH_SIZE])]);
+
+    assert_eq!(table.to_bytes(), input);
+    assert!(RoundTable::from_bytes(1_000, &input[..input.len() - 1]).is_err());
+    assert!(RoundTable::from_bytes(1_000, &[]).is_err());
+}
+
+#[test]
+fn test_bootstrap_table_from_bytes() {
+    let mut input = vec![1u8];
+    input.extend_from_slice(&[1u8; PUBLIC_KEY_SIZE]);
+
+    let table = RoundTable::from_bootstrap_bytes(1, &input).unwrap();
+    assert_eq!(table.confidants, vec![[1u8; PUBLIC_KEY_SIZE]]);
+    assert!(table.hashes.is_empty());
+    assert!(RoundTable::from_bootstrap_bytes(1, &input[..PUBLIC_KEY_SIZE]).is_err());
+}
@@ -0,0 +1,166 @@
+use std::collections::{BTreeMap, HashMap};
+
+use log::debug;
+
+use super::super::network::packet::MsgType;
+
+/// count of the last rounds to keep stage messages for
+const MAX_CACHED_ROUNDS: u64 = 5;
+/// max total size of cached stage messages
+const MAX_CACHED_BYTES: usize = 32 * 1024 * 1024;
+/// max count of messages of the same stage from one confidant per round
+const MAX_STAGE_MESSAGES: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Stage {
+    First,
+    Second,
+    Third,
+    SmartFirst,
+    SmartSecond,
+    SmartThird
+}
+
+impl Stage {
+
+    /// stage of the stage message
+    pub fn from_msg(msg: &MsgType) -> Option<Stage> {
+        match msg {
+            MsgType::FirstStage => Some(Stage::First),
+            MsgType::SecondStage => Some(Stage::Second),
+            MsgType::ThirdStage => Some(Stage::Third),
+            MsgType::FirstSmartStage => Some(Stage::SmartFirst),
+            MsgType::SecondSmartStage => Some(Stage::SmartSecond),
+            MsgType::ThirdSmartStage => Some(Stage::SmartThird),
+            _ => None
+        }
+    }
+
+    /// stage requested by the stage request message
+    pub fn from_request(msg: &MsgType) -> Option<Stage> {
+        match msg {
+            MsgType::FirstStageRequest => Some(Stage::First),
+            MsgType::SecondStageRequest => Some(Stage::Second),
+            MsgType::ThirdStageRequest => Some(Stage::Third),
+            MsgType::SmartFirstStageRequest => Some(Stage::SmartFirst),
+            MsgType::SmartSecondStageRequest => Some(Stage::SmartSecond),
+            MsgType::SmartThirdStageRequest => Some(Stage::SmartThird),
+            _ => None
+        }
+    }
+
+    /// message type to send the stage
+    pub fn msg(&self) -> MsgType {
+        match self {
+            Stage::First => MsgType::FirstStage,
+            Stage::Second => MsgType::SecondStage,
+            Stage::Third => MsgType::ThirdStage,
+            Stage::SmartFirst => MsgType::FirstSmartStage,
+            Stage::SmartSecond => MsgType::SecondSmartStage,
+            Stage::SmartThird => MsgType::ThirdSmartStage
+        }
+    }
+}
+
+/// Stage messages of the last rounds received and sent, indexed by round, stage and confidant index
+pub struct StageCache {
+    rounds: BTreeMap<u64, HashMap<(Stage, u8), Vec<Vec<u8>>>>,
+    bytes: usize
+}
+
+impl StageCache {
+
+    pub fn new() -> StageCache {
+        StageCache {
+            rounds: BTreeMap::new(),
+            bytes: 0
+        }
+    }
+
+    /// stores stage message of confidant, smart stages may be stored several times per round;
+    /// returns false if the message is dropped as duplicate or exceeding limits
+    pub fn store(&mut self, rnd: u64, stage: Stage, confidant: u8, payload: &[u8]) -> bool {
+        let messages = self.rounds.entry(rnd).or_insert_with(HashMap::new).entry((stage, confidant)).or_insert_with(Vec::new);
+        if messages.iter().any(|m| &m[..] == payload) {
+            return false;
+        }
+        if messages.len() >= MAX_STAGE_MESSAGES {
+            debug!("too many {:?} stages of [{}] in R {}, drop", stage, confidant, rnd);
+            return false;
+        }
+        messages.push(payload.to_vec());
+        self.bytes += payload.len();
+        self.evict();
+        if self.bytes > MAX_CACHED_BYTES {
+            // the latest round alone exceeds the limit
+            if let Some(m) = self.rounds.get_mut(&rnd).and_then(|r| r.get_mut(&(stage, confidant))) {
+                m.pop();
+                self.bytes -= payload.len();
+            }
+            debug!("stage messages of R {} exceed {} bytes, drop", rnd, MAX_CACHED_BYTES);
+            return false;
+        }
+        true
+    }
+
+    pub fn get(&self, rnd: u64, stage: Stage, confidant: u8) -> Option<&Vec<Vec<u8>>> {
+        self.rounds.get(&rnd)?.get(&(stage, confidant))
+    }
+
+    /// stage messages of round rnd are kept while the current round is last
+    pub fn keeps(rnd: u64, last: u64) -> bool {
+        rnd + MAX_CACHED_ROUNDS > last
+    }
+
+    fn evict(&mut self) {
+        let last = match self.rounds.keys().next_back() {
+            None => return,
+            Some(r) => *r
+        };
+        while let Some(oldest) = self.rounds.keys().next().cloned() {
+            if StageCache::keeps(oldest, last) && self.bytes <= MAX_CACHED_BYTES {
+                break;
+            }
+            if oldest == last {
+                // never drop the latest round
+                break;
+            }
+            if let Some(items) = self.rounds.remove(&oldest) {
+                let size: usize = items.values().flatten().map(|m| m.len()).sum();
+                self.bytes -= size;
+                debug!("stage messages of R {} are evicted, {} bytes", oldest, size);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_stage_cache() {
+    let mut cache = StageCache::new();
+    cache.store(10, Stage::First, 0, &[1u8, 2u8]);
+    cache.store(10, Stage::First, 0, &[1u8, 2u8]);
+    cache.store(10, Stage::SmartFirst, 1, &[3u8]);
+    cache.store(10, Stage::SmartFirst, 1, &[4u8]);
+    assert_eq!(cache.get(10, Stage::First, 0).unwrap().len(), 1);
+    assert_eq!(cache.get(10, Stage::SmartFirst, 1).unwrap().len(), 2);
+    assert!(cache.get(10, Stage::Second, 0).is_none());
+    assert_eq!(cache.bytes, 4);
+
+    assert!(StageCache::keeps(10, 10 + MAX_CACHED_ROUNDS - 1));
+    cache.store(10 + MAX_CACHED_ROUNDS, Stage::Third, 2, &[5u8]);
+    assert!(!StageCache::keeps(10, 10 + MAX_CACHED_ROUNDS));
+    assert!(cache.get(10, Stage::First, 0).is_none());
+    assert_eq!(cache.bytes, 1);
+
+    // limits are enforced within the latest round
+    let rnd = 10 + MAX_CACHED_ROUNDS;
+    for i in 0..MAX_STAGE_MESSAGES {
+        assert!(cache.store(rnd, Stage::SmartFirst, 3, &[i as u8]));
+    }
+    assert!(!cache.store(rnd, Stage::SmartFirst, 3, &[0xffu8]));
+    let big = vec![0u8; MAX_CACHED_BYTES];
+    assert!(!cache.store(rnd, Stage::Second, 4, &big));
+    assert!(cache.get(rnd, Stage::Second, 4).unwrap().is_empty());
+    assert_eq!(cache.bytes, 1 + MAX_STAGE_MESSAGES);
+    assert_eq!(Stage::from_request(&MsgType::SmartThirdStageRequest).unwrap().msg() as u8, MsgType::ThirdSmartStage as u8);
+}
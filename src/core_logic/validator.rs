@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+
+use super::super::PublicKey;
+use super::super::csdb::{Amount, Transaction, Wallets, WalletData};
+
+extern crate ed25519_dalek;
+use ed25519_dalek::Verifier;
+
+/// min fee the source must agree to pay, 0.0087 CS as kMinFee of the original node fee.cpp
+const MIN_FEE: Amount = Amount {
+    integral: 0,
+    fraction: 8_700_000_000_000_000
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectReason {
+    /// source wallet id is not known
+    UnknownSource,
+    WrongSignature,
+    /// max fee is below the min one
+    InsufficientFee,
+    /// inner id is already used by the source
+    InnerIdReplay,
+    InsufficientBalance
+}
+
+impl fmt::Display for RejectReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// Checks transactions against the wallet state in stages: signature, fee, inner id, balance.
+/// Accepted transactions are accounted, so the following ones of the same source see the spent balance.
+pub struct Validator<'a> {
+    wallets: &'a Wallets,
+    /// wallets of sources changed by accepted transactions
+    pending: HashMap<PublicKey, WalletData>
+}
+
+impl<'a> Validator<'a> {
+
+    pub fn new(wallets: &'a Wallets) -> Validator<'a> {
+        Validator {
+            wallets: wallets,
+            pending: HashMap::new()
+        }
+    }
+
+    pub fn validate(&mut self, t: &Transaction) -> Result<(), RejectReason> {
+        let source = self.wallets.resolve(&t.source).ok_or(RejectReason::UnknownSource)?;
+        test_signature(&source, t)?;
+        test_fee(t)?;
+        let mut wallet = match self.pending.get(&source) {
+            Some(w) => w.clone(),
+            None => self.wallets.get(&source).cloned().unwrap_or_default()
+        };
+        if let Some(last) = wallet.last_inner_id {
+            if t.inner_id <= last {
+                return Err(RejectReason::InnerIdReplay);
+            }
+        }
+        wallet.balance = t.amount.checked_add(&t.max_fee.to_amount())
+            .and_then(|spent| wallet.balance.checked_sub(&spent))
+            .filter(|rest| rest.integral >= 0)
+            .ok_or(RejectReason::InsufficientBalance)?;
+        wallet.last_inner_id = Some(t.inner_id);
+        self.pending.insert(source, wallet);
+        Ok(())
+    }
+}
+
+fn test_signature(source: &PublicKey, t: &Transaction) -> Result<(), RejectReason> {
+    let key = ed25519_dalek::PublicKey::from_bytes(source).map_err(|_| RejectReason::WrongSignature)?;
+    let signature = ed25519_dalek::Signature::try_from(&t.signature[..]).map_err(|_| RejectReason::WrongSignature)?;
+    key.verify(&t.signing_bytes(), &signature).map_err(|_| RejectReason::WrongSignature)
+}
+
+fn test_fee(t: &Transaction) -> Result<(), RejectReason> {
+    if t.max_fee.to_amount() < MIN_FEE {
+        return Err(RejectReason::InsufficientFee);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_validator() {
+    use ed25519_dalek::{ExpandedSecretKey, SecretKey};
+    use super::super::csdb::{Address, Amount, Fee};
+
+    let secret = SecretKey::from_bytes(&[9u8; 32]).unwrap();
+    let public = ed25519_dalek::PublicKey::from(&secret);
+    let source = public.to_bytes();
+    let sign = |t: &mut Transaction| {
+        t.signature = ExpandedSecretKey::from(&secret).sign(&t.signing_bytes(), &public).to_bytes();
+    };
+
+    let mut wallets = Wallets::new();
+    wallets.set(&source, WalletData {
+        balance: Amount::new(10, 0),
+        last_inner_id: Some(4)
+    });
+    let mut t = super::super::csdb::test_transaction();
+    t.source = Address::PublicKey(source);
+    // 0.01
+    t.max_fee = Fee((16 << 10) | 1023);
+    t.amount = Amount::new(6, 0);
+    sign(&mut t);
+
+    let mut validator = Validator::new(&wallets);
+    assert_eq!(validator.validate(&t), Ok(()));
+    // the same inner id again
+    assert_eq!(validator.validate(&t), Err(RejectReason::InnerIdReplay));
+    // 4 left only
+    t.inner_id = 6;
+    sign(&mut t);
+    assert_eq!(validator.validate(&t), Err(RejectReason::InsufficientBalance));
+    t.amount = Amount::new(3, 0);
+    // 890 / 1024 * 0.01 is just below the min fee
+    t.max_fee = Fee((16 << 10) | 890);
+    sign(&mut t);
+    assert_eq!(validator.validate(&t), Err(RejectReason::InsufficientFee));
+    t.amount = Amount::new(4, 0);
+    assert_eq!(validator.validate(&t), Err(RejectReason::WrongSignature));
+    t.source = Address::WalletId(1);
+    assert_eq!(validator.validate(&t), Err(RejectReason::UnknownSource));
+}
@@ -0,0 +1,117 @@
+use std::fmt;
+
+/// 10^18, fraction is counted in these parts of one
+pub const AMOUNT_FRACTION_MAX: u64 = 1_000_000_000_000_000_000;
+
+/// Copy of csdb::Amount: integral part plus fraction in 1e-18 units,
+/// fraction is always non-negative, so -0.25 is stored as -1 + 0.75
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Hash)]
+pub struct Amount {
+    pub integral: i32,
+    pub fraction: u64
+}
+
+impl Amount {
+
+    pub fn new(integral: i32, fraction: u64) -> Amount {
+        Amount {
+            integral: integral + (fraction / AMOUNT_FRACTION_MAX) as i32,
+            fraction: fraction % AMOUNT_FRACTION_MAX
+        }
+    }
+
+    pub fn checked_add(&self, other: &Amount) -> Option<Amount> {
+        let fraction = self.fraction + other.fraction;
+        let integral = self.integral.checked_add(other.integral)?.checked_add((fraction / AMOUNT_FRACTION_MAX) as i32)?;
+        Some(Amount {
+            integral: integral,
+            fraction: fraction % AMOUNT_FRACTION_MAX
+        })
+    }
+
+    pub fn checked_sub(&self, other: &Amount) -> Option<Amount> {
+        if self.fraction >= other.fraction {
+            Some(Amount {
+                integral: self.integral.checked_sub(other.integral)?,
+                fraction: self.fraction - other.fraction
+            })
+        }
+        else {
+            Some(Amount {
+                integral: self.integral.checked_sub(other.integral)?.checked_sub(1)?,
+                fraction: self.fraction + AMOUNT_FRACTION_MAX - other.fraction
+            })
+        }
+    }
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.integral < 0 && self.fraction > 0 {
+            // -1 + 0.75 = -0.25
+            let fraction = format!("{:018}", AMOUNT_FRACTION_MAX - self.fraction);
+            write!(f, "-{}.{}", -(self.integral + 1), fraction.trim_end_matches('0'))
+        }
+        else if self.fraction > 0 {
+            let fraction = format!("{:018}", self.fraction);
+            write!(f, "{}.{}", self.integral, fraction.trim_end_matches('0'))
+        }
+        else {
+            write!(f, "{}", self.integral)
+        }
+    }
+}
+
+/// Copy of csdb::AmountCommission: 16-bit float, sign(1) + exponent(5) + fraction(10),
+/// value = fraction / 1024 * 10^(exponent - 18)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Fee(pub u16);
+
+impl Fee {
+
+    /// fee as amount to withdraw, fraction is rounded to 1e-18
+    pub fn to_amount(&self) -> Amount {
+        // fraction / 1024 * 10^exponent in 1e-18 units
+        let fraction = (self.0 & 0x3ff) as u128;
+        let exponent = ((self.0 >> 10) & 0x1f) as u32;
+        let units = (fraction * 10u128.pow(exponent) + 512) / 1024;
+        let integral = std::cmp::min(units / AMOUNT_FRACTION_MAX as u128, std::i32::MAX as u128) as i32;
+        let value = Amount::new(integral, (units % AMOUNT_FRACTION_MAX as u128) as u64);
+        if self.0 & 0x8000 != 0 {
+            Amount::default().checked_sub(&value).unwrap_or_default()
+        }
+        else {
+            value
+        }
+    }
+}
+
+#[test]
+fn test_amount_display() {
+    assert_eq!(Amount::new(12, 0).to_string(), "12");
+    assert_eq!(Amount::new(0, 250_000_000_000_000_000).to_string(), "0.25");
+    assert_eq!(Amount::new(-1, 750_000_000_000_000_000).to_string(), "-0.25");
+    assert_eq!(Amount::new(1, AMOUNT_FRACTION_MAX + 1), Amount { integral: 2, fraction: 1 });
+    assert!(Amount::new(-1, 750_000_000_000_000_000) < Amount::new(0, 1));
+}
+
+#[test]
+fn test_amount_arithmetic() {
+    let a = Amount::new(1, 750_000_000_000_000_000);
+    let b = Amount::new(0, 500_000_000_000_000_000);
+    assert_eq!(a.checked_add(&b), Some(Amount::new(2, 250_000_000_000_000_000)));
+    assert_eq!(b.checked_sub(&a), Some(Amount::new(-2, 750_000_000_000_000_000)));
+    assert_eq!(a.checked_sub(&b).unwrap().checked_add(&b), Some(a));
+    assert_eq!(Amount::new(std::i32::MAX, 0).checked_add(&a), None);
+}
+
+#[test]
+fn test_fee_to_amount() {
+    assert_eq!(Fee(0).to_amount(), Amount::default());
+    // exponent 16, fraction 512: 0.5 * 10^-2
+    assert_eq!(Fee((16 << 10) | 512).to_amount(), Amount::new(0, 5_000_000_000_000_000));
+    assert_eq!(Fee((18 << 10) | 256).to_amount(), Amount::new(0, 250_000_000_000_000_000));
+    assert_eq!(Fee(0x8000 | (18 << 10) | 256).to_amount(), Amount::new(-1, 750_000_000_000_000_000));
+    // 1023 / 1024 * 10^-18 is rounded to 1e-18
+    assert_eq!(Fee(1023).to_amount(), Amount::new(0, 1));
+}
@@ -0,0 +1,67 @@
+use std::convert::TryInto;
+use std::fmt;
+
+use super::super::HASH_SIZE;
+
+extern crate base58;
+use base58::ToBase58;
+extern crate blake2s_simd;
+use blake2s_simd::blake2s;
+
+/// Copy of csdb::PoolHash and cs::TransactionsPacketHash: blake2s hash of the data
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Hash(pub [u8; HASH_SIZE]);
+
+impl Hash {
+
+    /// blake2s hash of bytes
+    pub fn of(bytes: &[u8]) -> Hash {
+        Hash(*blake2s(bytes).as_array())
+    }
+
+    pub fn from_slice(bytes: &[u8]) -> Option<Hash> {
+        bytes.try_into().ok().map(Hash)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// hash of nothing, the previous hash of the genesis block
+    pub fn is_zero(&self) -> bool {
+        self.0.iter().all(|b| *b == 0)
+    }
+
+    pub fn to_base58(&self) -> String {
+        self.0.to_base58()
+    }
+}
+
+/// hex by default, base58 in alternate form {:#}
+impl fmt::Display for Hash {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if f.alternate() {
+            write!(f, "{}", self.to_base58())
+        }
+        else {
+            write!(f, "{}", hex::encode(&self.0))
+        }
+    }
+}
+
+impl fmt::Debug for Hash {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Hash({})", hex::encode(&self.0))
+    }
+}
+
+#[test]
+fn test_hash_display() {
+    let h = Hash::of(b"abc");
+    // blake2s-256 test vector from RFC 7693
+    assert_eq!(h.to_string(), "508c5e8c327c14e2e1a72ba34eeb452f37458b209ed63a294d999b4c86675982");
+    assert_eq!(format!("{:#}", Hash([0u8; HASH_SIZE])), "11111111111111111111111111111111");
+    assert_eq!(Hash::from_slice(h.as_bytes()), Some(h));
+    assert!(Hash::from_slice(&[0u8; 3]).is_none());
+    assert!(Hash::default().is_zero() && !h.is_zero());
+}
@@ -0,0 +1,20 @@
+// Data model compatible with the csdb library of the original node
+
+mod amount;
+pub use amount::Amount;
+#[cfg(test)]
+pub use amount::Fee;
+mod hash;
+pub use hash::Hash;
+mod transaction;
+pub use transaction::Transaction;
+#[cfg(test)]
+pub use transaction::{Address, test_transaction};
+mod transactions_packet;
+pub use transactions_packet::TransactionsPacket;
+mod pool;
+pub use pool::Pool;
+#[cfg(test)]
+pub use pool::test_pool;
+mod wallets;
+pub use wallets::{Wallets, WalletData, BlockUndo};
@@ -0,0 +1,207 @@
+use std::collections::BTreeMap;
+use std::io::Read;
+
+use super::super::{PublicKey, HASH_SIZE, PUBLIC_KEY_SIZE};
+use super::amount::Amount;
+use super::hash::Hash;
+use super::transaction::{Signature, Transaction, UserField, SIGNATURE_SIZE};
+use super::transaction::{get_amount, get_user_fields, put_amount, put_user_fields};
+
+extern crate bincode;
+use bincode::deserialize_from;
+
+/// current binary format version of the pool
+const POOL_VERSION: u8 = 0;
+
+/// Copy of csdb::Pool::NewWalletInfo: short id assigned to the new wallet
+/// appeared as source or target of transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NewWallet {
+    /// index of the transaction in pool
+    pub transaction: u32,
+    /// the wallet is the target of the transaction, otherwise the source
+    pub is_target: bool,
+    pub wallet_id: u32
+}
+
+/// Copy of csdb::Pool, the block
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pool {
+    pub previous_hash: Hash,
+    pub sequence: u64,
+    pub user_fields: BTreeMap<u32, UserField>,
+    /// total fee of the round
+    pub round_cost: Amount,
+    pub transactions: Vec<Transaction>,
+    pub new_wallets: Vec<NewWallet>,
+    /// public keys of trusted nodes which have built the pool
+    pub confidants: Vec<PublicKey>,
+    /// signatures of confidants: confidant index + signature
+    pub signatures: Vec<(u8, Signature)>,
+    /// bit i is set if confidant i actually took part in consensus
+    pub real_trusted: u64
+}
+
+impl Pool {
+
+    #[cfg(test)]
+    pub fn new(previous_hash: Hash, sequence: u64) -> Pool {
+        Pool {
+            previous_hash: previous_hash,
+            sequence: sequence,
+            user_fields: BTreeMap::new(),
+            round_cost: Amount::default(),
+            transactions: Vec::new(),
+            new_wallets: Vec::new(),
+            confidants: Vec::new(),
+            signatures: Vec::new(),
+            real_trusted: 0
+        }
+    }
+
+    /// blake2s hash of the pool binary without signatures
+    pub fn hash(&self) -> Hash {
+        Hash::of(&self.hashed_bytes())
+    }
+
+    /// serializes as csdb::Pool::to_binary() does
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut output = self.hashed_bytes();
+        output.push(self.signatures.len() as u8);
+        for (index, signature) in &self.signatures {
+            output.push(*index);
+            output.extend_from_slice(signature);
+        }
+        output
+    }
+
+    fn hashed_bytes(&self) -> Vec<u8> {
+        /*
+            version(1) + previous hash(32) + sequence(8)
+            user fields count(1) + user fields
+            round cost(12)
+            transactions count(4) + transactions
+            new wallets count(4) + new wallets: transaction index(4) + is target(1) + wallet id(4)
+            confidants count(1) + confidants(32 each)
+            real trusted mask(8)
+        */
+        let mut output = Vec::<u8>::with_capacity(1 + HASH_SIZE + 8 + 1 + 12 + 4 + 4 + 1 + self.confidants.len() * PUBLIC_KEY_SIZE + 8);
+        output.push(POOL_VERSION);
+        output.extend_from_slice(self.previous_hash.as_bytes());
+        output.extend_from_slice(&self.sequence.to_le_bytes());
+        put_user_fields(&mut output, &self.user_fields);
+        put_amount(&mut output, &self.round_cost);
+        output.extend_from_slice(&(self.transactions.len() as u32).to_le_bytes());
+        for t in &self.transactions {
+            output.extend_from_slice(&t.to_bytes());
+        }
+        output.extend_from_slice(&(self.new_wallets.len() as u32).to_le_bytes());
+        for w in &self.new_wallets {
+            output.extend_from_slice(&w.transaction.to_le_bytes());
+            output.push(w.is_target as u8);
+            output.extend_from_slice(&w.wallet_id.to_le_bytes());
+        }
+        output.push(self.confidants.len() as u8);
+        for key in &self.confidants {
+            output.extend_from_slice(key);
+        }
+        output.extend_from_slice(&self.real_trusted.to_le_bytes());
+        output
+    }
+
+    /// deserializes the whole input, returns the pool and its hash
+    pub fn from_bytes(bytes: &[u8]) -> bincode::Result<(Pool, Hash)> {
+        let mut input = bytes;
+        let version: u8 = deserialize_from(&mut input)?;
+        if version != POOL_VERSION {
+            return Err(Box::new(bincode::ErrorKind::Custom(format!("unsupported pool version {}", version))));
+        }
+        let previous_hash: [u8; HASH_SIZE] = deserialize_from(&mut input)?;
+        let sequence: u64 = deserialize_from(&mut input)?;
+        let user_fields = get_user_fields(&mut input)?;
+        let round_cost = get_amount(&mut input)?;
+
+        let count: u32 = deserialize_from(&mut input)?;
+        let mut transactions = Vec::<Transaction>::new();
+        for _ in 0..count {
+            transactions.push(Transaction::from_bytes(&mut input)?);
+        }
+        let count: u32 = deserialize_from(&mut input)?;
+        let mut new_wallets = Vec::<NewWallet>::new();
+        for _ in 0..count {
+            let transaction: u32 = deserialize_from(&mut input)?;
+            let is_target: u8 = deserialize_from(&mut input)?;
+            let wallet_id: u32 = deserialize_from(&mut input)?;
+            new_wallets.push(NewWallet {
+                transaction: transaction,
+                is_target: is_target != 0,
+                wallet_id: wallet_id
+            });
+        }
+        let count: u8 = deserialize_from(&mut input)?;
+        let mut confidants = Vec::<PublicKey>::with_capacity(count as usize);
+        for _ in 0..count {
+            confidants.push(deserialize_from(&mut input)?);
+        }
+        let real_trusted: u64 = deserialize_from(&mut input)?;
+        let hash = Hash::of(&bytes[..bytes.len() - input.len()]);
+
+        let count: u8 = deserialize_from(&mut input)?;
+        let mut signatures = Vec::<(u8, Signature)>::with_capacity(count as usize);
+        for _ in 0..count {
+            let index: u8 = deserialize_from(&mut input)?;
+            let mut signature = [0u8; SIGNATURE_SIZE];
+            input.read_exact(&mut signature)?;
+            signatures.push((index, signature));
+        }
+        if !input.is_empty() {
+            return Err(Box::new(bincode::ErrorKind::Custom(format!("{} extra bytes after pool", input.len()))));
+        }
+
+        Ok((Pool {
+            previous_hash: Hash(previous_hash),
+            sequence: sequence,
+            user_fields: user_fields,
+            round_cost: round_cost,
+            transactions: transactions,
+            new_wallets: new_wallets,
+            confidants: confidants,
+            signatures: signatures,
+            real_trusted: real_trusted
+        }, hash))
+    }
+}
+
+#[cfg(test)]
+pub fn test_pool(previous_hash: Hash, sequence: u64) -> Pool {
+    let mut pool = Pool::new(previous_hash, sequence);
+    pool.transactions.push(super::transaction::test_transaction());
+    pool.new_wallets.push(NewWallet {
+        transaction: 0,
+        is_target: true,
+        wallet_id: 0x0102
+    });
+    pool.confidants = vec![[1u8; PUBLIC_KEY_SIZE], [2u8; PUBLIC_KEY_SIZE]];
+    pool.real_trusted = 0b11;
+    pool.signatures.push((1, [4u8; SIGNATURE_SIZE]));
+    pool
+}
+
+#[test]
+fn test_pool_bytes() {
+    let mut pool = test_pool(Hash([7u8; HASH_SIZE]), 42);
+    pool.user_fields.insert(0, UserField::Integer(-1));
+    pool.round_cost = Amount::new(0, 9_000_000_000_000_000);
+    let bytes = pool.to_bytes();
+    assert_eq!(bytes[0], POOL_VERSION);
+    assert_eq!(&bytes[1..1 + HASH_SIZE], &[7u8; HASH_SIZE]);
+    assert_eq!(&bytes[1 + HASH_SIZE..9 + HASH_SIZE], &42u64.to_le_bytes());
+
+    let (decoded, hash) = Pool::from_bytes(&bytes).unwrap();
+    assert_eq!(decoded, pool);
+    assert_eq!(hash, pool.hash());
+    // signatures do not affect the hash
+    pool.signatures.clear();
+    assert_eq!(pool.hash(), hash);
+    assert!(Pool::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+}
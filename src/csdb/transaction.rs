@@ -0,0 +1,259 @@
+use std::collections::BTreeMap;
+use std::io::Read;
+
+use super::super::{PublicKey, PUBLIC_KEY_SIZE};
+use super::amount::{Amount, Fee};
+use super::hash::Hash;
+
+extern crate bincode;
+use bincode::deserialize_from;
+
+pub const SIGNATURE_SIZE: usize = 64;
+pub type Signature = [u8; SIGNATURE_SIZE];
+
+/// only the lower 46 bits of inner id are significant
+const INNER_ID_MASK: u64 = 0x3fff_ffff_ffff;
+/// inner id flag: source is set as wallet id
+const SOURCE_IS_ID: u64 = 0x8000_0000_0000;
+/// inner id flag: target is set as wallet id
+const TARGET_IS_ID: u64 = 0x4000_0000_0000;
+
+/// Copy of csdb::Address: either public key or short wallet id
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Address {
+    PublicKey(PublicKey),
+    WalletId(u32)
+}
+
+/// Copy of csdb::UserField
+#[derive(Debug, Clone, PartialEq)]
+pub enum UserField {
+    Integer(i64),
+    String(Vec<u8>),
+    Amount(Amount)
+}
+
+impl UserField {
+    // copy of csdb::UserField::Type
+    const INTEGER: u8 = 1;
+    const STRING: u8 = 2;
+    const AMOUNT: u8 = 3;
+}
+
+/// Copy of csdb::Transaction
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transaction {
+    /// sequential number of transaction from the source, 46 bits
+    pub inner_id: u64,
+    pub source: Address,
+    pub target: Address,
+    pub amount: Amount,
+    /// max fee the source agrees to pay
+    pub max_fee: Fee,
+    /// fee counted by the network, is not signed
+    pub counted_fee: Fee,
+    pub currency: u8,
+    /// user fields ordered by id
+    pub user_fields: BTreeMap<u32, UserField>,
+    /// signature of the source
+    pub signature: Signature
+}
+
+impl Transaction {
+
+    /// serializes as csdb::Transaction::put() does
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut output = self.signing_bytes();
+        output.extend_from_slice(&self.signature);
+        output.extend_from_slice(&self.counted_fee.0.to_le_bytes());
+        output
+    }
+
+    /// data the source signs
+    pub fn signing_bytes(&self) -> Vec<u8> {
+        /*
+            innerID(6) with source/target flags
+            source: wallet id(4) or public key(32)
+            target: wallet id(4) or public key(32)
+            amount: integral(4) + fraction(8)
+            max fee(2)
+            currency(1)
+            user fields count(1) + user fields: id(4) + type(1) + value
+        */
+        let mut output = Vec::<u8>::with_capacity(6 + 2 * PUBLIC_KEY_SIZE + 12 + 2 + 1 + 1);
+        let mut inner_id = self.inner_id & INNER_ID_MASK;
+        if let Address::WalletId(_) = self.source {
+            inner_id |= SOURCE_IS_ID;
+        }
+        if let Address::WalletId(_) = self.target {
+            inner_id |= TARGET_IS_ID;
+        }
+        output.extend_from_slice(&inner_id.to_le_bytes()[..6]);
+        put_address(&mut output, &self.source);
+        put_address(&mut output, &self.target);
+        put_amount(&mut output, &self.amount);
+        output.extend_from_slice(&self.max_fee.0.to_le_bytes());
+        output.push(self.currency);
+        put_user_fields(&mut output, &self.user_fields);
+        output
+    }
+
+    /// blake2s hash of the signed data
+    pub fn hash(&self) -> Hash {
+        Hash::of(&self.signing_bytes())
+    }
+
+    /// deserializes from input, input is advanced past the transaction
+    pub fn from_bytes(input: &mut &[u8]) -> bincode::Result<Transaction> {
+        let mut id_bytes = [0u8; 8];
+        input.read_exact(&mut id_bytes[..6])?;
+        let inner_id = u64::from_le_bytes(id_bytes);
+        let source = get_address(input, inner_id & SOURCE_IS_ID != 0)?;
+        let target = get_address(input, inner_id & TARGET_IS_ID != 0)?;
+        let amount = get_amount(input)?;
+        let max_fee: u16 = deserialize_from(&mut *input)?;
+        let currency: u8 = deserialize_from(&mut *input)?;
+        let user_fields = get_user_fields(input)?;
+        let mut signature = [0u8; SIGNATURE_SIZE];
+        input.read_exact(&mut signature)?;
+        let counted_fee: u16 = deserialize_from(&mut *input)?;
+
+        Ok(Transaction {
+            inner_id: inner_id & INNER_ID_MASK,
+            source: source,
+            target: target,
+            amount: amount,
+            max_fee: Fee(max_fee),
+            counted_fee: Fee(counted_fee),
+            currency: currency,
+            user_fields: user_fields,
+            signature: signature
+        })
+    }
+}
+
+pub(super) fn put_address(output: &mut Vec<u8>, addr: &Address) {
+    match addr {
+        Address::PublicKey(key) => output.extend_from_slice(key),
+        Address::WalletId(id) => output.extend_from_slice(&id.to_le_bytes())
+    }
+}
+
+/// user fields count(1) + user fields: id(4) + type(1) + value
+pub(super) fn put_user_fields(output: &mut Vec<u8>, user_fields: &BTreeMap<u32, UserField>) {
+    output.push(user_fields.len() as u8);
+    for (id, field) in user_fields {
+        output.extend_from_slice(&id.to_le_bytes());
+        match field {
+            UserField::Integer(v) => {
+                output.push(UserField::INTEGER);
+                output.extend_from_slice(&v.to_le_bytes());
+            }
+            UserField::String(v) => {
+                output.push(UserField::STRING);
+                output.extend_from_slice(&(v.len() as u32).to_le_bytes());
+                output.extend_from_slice(v);
+            }
+            UserField::Amount(v) => {
+                output.push(UserField::AMOUNT);
+                put_amount(output, v);
+            }
+        }
+    }
+}
+
+pub(super) fn get_user_fields(input: &mut &[u8]) -> bincode::Result<BTreeMap<u32, UserField>> {
+    let fields_count: u8 = deserialize_from(&mut *input)?;
+    let mut user_fields = BTreeMap::<u32, UserField>::new();
+    for _ in 0..fields_count {
+        let id: u32 = deserialize_from(&mut *input)?;
+        let field_type: u8 = deserialize_from(&mut *input)?;
+        let field = match field_type {
+            UserField::INTEGER => UserField::Integer(deserialize_from(&mut *input)?),
+            UserField::STRING => {
+                let len: u32 = deserialize_from(&mut *input)?;
+                if len as usize > input.len() {
+                    return Err(Box::new(bincode::ErrorKind::Custom(format!("user field {} length {} exceeds data", id, len))));
+                }
+                let mut v = vec![0u8; len as usize];
+                input.read_exact(&mut v)?;
+                UserField::String(v)
+            }
+            UserField::AMOUNT => UserField::Amount(get_amount(input)?),
+            _ => {
+                return Err(Box::new(bincode::ErrorKind::Custom(format!("unknown user field type {}", field_type))));
+            }
+        };
+        user_fields.insert(id, field);
+    }
+    Ok(user_fields)
+}
+
+pub(super) fn put_amount(output: &mut Vec<u8>, amount: &Amount) {
+    output.extend_from_slice(&amount.integral.to_le_bytes());
+    output.extend_from_slice(&amount.fraction.to_le_bytes());
+}
+
+pub(super) fn get_address(input: &mut &[u8], is_id: bool) -> bincode::Result<Address> {
+    if is_id {
+        Ok(Address::WalletId(deserialize_from(&mut *input)?))
+    }
+    else {
+        Ok(Address::PublicKey(deserialize_from(&mut *input)?))
+    }
+}
+
+pub(super) fn get_amount(input: &mut &[u8]) -> bincode::Result<Amount> {
+    let integral: i32 = deserialize_from(&mut *input)?;
+    let fraction: u64 = deserialize_from(&mut *input)?;
+    Ok(Amount {
+        integral: integral,
+        fraction: fraction
+    })
+}
+
+#[cfg(test)]
+pub fn test_transaction() -> Transaction {
+    let mut user_fields = BTreeMap::<u32, UserField>::new();
+    user_fields.insert(1, UserField::String(b"memo".to_vec()));
+    Transaction {
+        inner_id: 5,
+        source: Address::PublicKey([1u8; PUBLIC_KEY_SIZE]),
+        target: Address::WalletId(0x0102),
+        amount: Amount::new(10, 500_000_000_000_000_000),
+        max_fee: Fee(0x4a00),
+        counted_fee: Fee(0x4800),
+        currency: 1,
+        user_fields: user_fields,
+        signature: [2u8; SIGNATURE_SIZE]
+    }
+}
+
+#[test]
+fn test_transaction_bytes() {
+    let t = test_transaction();
+    let mut expected = Vec::<u8>::new();
+    // inner id 5 with target-is-id flag
+    expected.extend_from_slice(&[0x05, 0x00, 0x00, 0x00, 0x00, 0x40]);
+    expected.extend_from_slice(&[1u8; PUBLIC_KEY_SIZE]);
+    expected.extend_from_slice(&[0x02, 0x01, 0x00, 0x00]);
+    // 10.5
+    expected.extend_from_slice(&[0x0a, 0x00, 0x00, 0x00]);
+    expected.extend_from_slice(&[0x00, 0x00, 0xb2, 0xd3, 0x59, 0x5b, 0xf0, 0x06]);
+    expected.extend_from_slice(&[0x00, 0x4a]);
+    expected.push(0x01);
+    // one string user field "memo"
+    expected.extend_from_slice(&[0x01, 0x01, 0x00, 0x00, 0x00, 0x02, 0x04, 0x00, 0x00, 0x00]);
+    expected.extend_from_slice(b"memo");
+    assert_eq!(t.signing_bytes(), expected);
+
+    expected.extend_from_slice(&[2u8; SIGNATURE_SIZE]);
+    expected.extend_from_slice(&[0x00, 0x48]);
+    assert_eq!(t.to_bytes(), expected);
+
+    let mut input = &expected[..];
+    assert_eq!(Transaction::from_bytes(&mut input).unwrap(), t);
+    assert!(input.is_empty());
+    assert!(Transaction::from_bytes(&mut &expected[..expected.len() - 1]).is_err());
+    assert_eq!(t.hash(), Hash::of(&t.signing_bytes()));
+}
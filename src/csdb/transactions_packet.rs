@@ -0,0 +1,103 @@
+use std::io::Read;
+
+use super::hash::Hash;
+use super::transaction::{Signature, Transaction, SIGNATURE_SIZE};
+
+extern crate bincode;
+use bincode::deserialize_from;
+
+/// Copy of csdb::TransactionsPacket
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransactionsPacket {
+    /// blake2s hash of the packet without signatures
+    pub hash: Hash,
+    pub transactions: Vec<Transaction>,
+    /// signatures of the packet by confidants: confidant index + signature
+    pub signatures: Vec<(u8, Signature)>
+}
+
+impl TransactionsPacket {
+
+    #[cfg(test)]
+    pub fn new(transactions: Vec<Transaction>) -> TransactionsPacket {
+        let mut packet = TransactionsPacket {
+            hash: Hash::default(),
+            transactions: transactions,
+            signatures: Vec::new()
+        };
+        packet.hash = Hash::of(&packet.hashed_bytes());
+        packet
+    }
+
+    #[cfg(test)]
+    /// serializes as csdb::TransactionsPacket::toBinary() does
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut output = self.hashed_bytes();
+        output.push(self.signatures.len() as u8);
+        for (index, signature) in &self.signatures {
+            output.push(*index);
+            output.extend_from_slice(signature);
+        }
+        output
+    }
+
+    #[cfg(test)]
+    /// transactions count(4) + transactions, the part the packet hash is taken from
+    fn hashed_bytes(&self) -> Vec<u8> {
+        let mut output = Vec::<u8>::new();
+        output.extend_from_slice(&(self.transactions.len() as u32).to_le_bytes());
+        for t in &self.transactions {
+            output.extend_from_slice(&t.to_bytes());
+        }
+        output
+    }
+
+    /// deserializes the whole input and calculates the packet hash
+    pub fn from_bytes(bytes: &[u8]) -> bincode::Result<TransactionsPacket> {
+        /*
+            transactions count(4) + transactions
+            signatures count(1) + signatures: index(1) + signature(64)
+        */
+        let mut input = bytes;
+        let count: u32 = deserialize_from(&mut input)?;
+        let mut transactions = Vec::<Transaction>::new();
+        for _ in 0..count {
+            transactions.push(Transaction::from_bytes(&mut input)?);
+        }
+        let hash = Hash::of(&bytes[..bytes.len() - input.len()]);
+
+        let sig_count: u8 = deserialize_from(&mut input)?;
+        let mut signatures = Vec::<(u8, Signature)>::with_capacity(sig_count as usize);
+        for _ in 0..sig_count {
+            let index: u8 = deserialize_from(&mut input)?;
+            let mut signature = [0u8; SIGNATURE_SIZE];
+            input.read_exact(&mut signature)?;
+            signatures.push((index, signature));
+        }
+        if !input.is_empty() {
+            return Err(Box::new(bincode::ErrorKind::Custom(format!("{} extra bytes after transactions packet", input.len()))));
+        }
+
+        Ok(TransactionsPacket {
+            hash: hash,
+            transactions: transactions,
+            signatures: signatures
+        })
+    }
+}
+
+#[test]
+fn test_transactions_packet_bytes() {
+    let mut packet = TransactionsPacket::new(vec![super::transaction::test_transaction(); 2]);
+    let unsigned = packet.to_bytes();
+    packet.signatures.push((3, [7u8; SIGNATURE_SIZE]));
+    let bytes = packet.to_bytes();
+    assert_eq!(&bytes[..4], &[0x02, 0x00, 0x00, 0x00]);
+    assert_eq!(bytes.len(), unsigned.len() + 1 + SIGNATURE_SIZE);
+
+    let decoded = TransactionsPacket::from_bytes(&bytes).unwrap();
+    assert_eq!(decoded, packet);
+    // signatures do not affect the hash
+    assert_eq!(decoded.hash, TransactionsPacket::from_bytes(&unsigned).unwrap().hash);
+    assert!(TransactionsPacket::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+}
@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+
+use super::super::PublicKey;
+use super::amount::Amount;
+use super::transaction::Address;
+use super::pool::Pool;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WalletData {
+    pub balance: Amount,
+    /// the latest inner id used by the wallet as a source
+    pub last_inner_id: Option<u64>
+}
+
+/// Wallet states before the block is applied, restores them on rollback
+pub struct BlockUndo {
+    pub sequence: u64,
+    /// wallets changed by the block and their previous data, None for new ones
+    wallets: Vec<(PublicKey, Option<WalletData>)>,
+    /// wallet ids assigned by the block and their previous keys, None for new ones
+    ids: Vec<(u32, Option<PublicKey>)>
+}
+
+/// Wallet balances and inner ids known from stored blocks
+pub struct Wallets {
+    wallets: HashMap<PublicKey, WalletData>,
+    /// short wallet ids assigned by the network
+    ids: HashMap<u32, PublicKey>
+}
+
+impl Wallets {
+
+    pub fn new() -> Wallets {
+        Wallets {
+            wallets: HashMap::new(),
+            ids: HashMap::new()
+        }
+    }
+
+    /// public key of the address, None for unknown wallet id
+    pub fn resolve(&self, addr: &Address) -> Option<PublicKey> {
+        match addr {
+            Address::PublicKey(key) => Some(*key),
+            Address::WalletId(id) => self.ids.get(id).cloned()
+        }
+    }
+
+    pub fn get(&self, key: &PublicKey) -> Option<&WalletData> {
+        self.wallets.get(key)
+    }
+
+    #[cfg(test)]
+    pub fn set(&mut self, key: &PublicKey, data: WalletData) {
+        self.wallets.insert(*key, data);
+    }
+
+    pub fn set_id(&mut self, id: u32, key: &PublicKey) {
+        self.ids.insert(id, *key);
+    }
+
+    /// applies new wallet ids and transfers of the block, returns what to undo it
+    pub fn apply(&mut self, pool: &Pool) -> BlockUndo {
+        let mut undo = BlockUndo {
+            sequence: pool.sequence,
+            wallets: Vec::new(),
+            ids: Vec::new()
+        };
+        for w in &pool.new_wallets {
+            let key = match pool.transactions.get(w.transaction as usize) {
+                None => continue,
+                Some(t) => if w.is_target { &t.target } else { &t.source }
+            };
+            if let Address::PublicKey(key) = key {
+                if !undo.ids.iter().any(|(id, _)| *id == w.wallet_id) {
+                    undo.ids.push((w.wallet_id, self.ids.get(&w.wallet_id).cloned()));
+                }
+                self.set_id(w.wallet_id, key);
+            }
+        }
+        for t in &pool.transactions {
+            let (source, target) = match (self.resolve(&t.source), self.resolve(&t.target)) {
+                (Some(s), Some(t)) => (s, t),
+                _ => continue
+            };
+            self.save(&mut undo, &source);
+            self.save(&mut undo, &target);
+            let spent = t.amount.checked_add(&t.counted_fee.to_amount()).unwrap_or(t.amount);
+            let data = self.wallets.entry(source).or_default();
+            data.balance = data.balance.checked_sub(&spent).unwrap_or(data.balance);
+            data.last_inner_id = Some(t.inner_id);
+            let data = self.wallets.entry(target).or_default();
+            data.balance = data.balance.checked_add(&t.amount).unwrap_or(data.balance);
+        }
+        undo
+    }
+
+    /// restores wallets as they were before the block is applied
+    pub fn undo(&mut self, undo: BlockUndo) {
+        for (key, data) in undo.wallets {
+            match data {
+                None => self.wallets.remove(&key),
+                Some(d) => self.wallets.insert(key, d)
+            };
+        }
+        for (id, key) in undo.ids {
+            match key {
+                None => self.ids.remove(&id),
+                Some(k) => self.ids.insert(id, k)
+            };
+        }
+    }
+
+    fn save(&self, undo: &mut BlockUndo, key: &PublicKey) {
+        if !undo.wallets.iter().any(|(k, _)| k == key) {
+            undo.wallets.push((*key, self.wallets.get(key).cloned()));
+        }
+    }
+}
+
+#[test]
+fn test_wallets_resolve() {
+    let key = [3u8; 32];
+    let mut wallets = Wallets::new();
+    assert_eq!(wallets.resolve(&Address::WalletId(7)), None);
+    wallets.set_id(7, &key);
+    assert_eq!(wallets.resolve(&Address::WalletId(7)), Some(key));
+    assert_eq!(wallets.resolve(&Address::PublicKey(key)), Some(key));
+    assert!(wallets.get(&key).is_none());
+}
+
+#[test]
+fn test_wallets_apply_undo() {
+    use super::super::Hash;
+    use super::pool::{test_pool, NewWallet};
+    use super::transaction::test_transaction;
+
+    let mut pool = test_pool(Hash::default(), 1);
+    let mut t = test_transaction();
+    let source = [1u8; 32];
+    t.source = Address::PublicKey(source);
+    t.target = Address::WalletId(9);
+    t.inner_id = 5;
+    pool.transactions = vec![t.clone()];
+    pool.new_wallets = vec![
+        NewWallet { transaction: 0, is_target: false, wallet_id: 4 },
+        NewWallet { transaction: 0, is_target: false, wallet_id: 8 }
+    ];
+
+    let mut wallets = Wallets::new();
+    let target = [2u8; 32];
+    wallets.set_id(9, &target);
+    // the block overwrites id 8
+    wallets.set_id(8, &target);
+    wallets.set(&source, WalletData { balance: Amount::new(10, 0), last_inner_id: Some(4) });
+    let undo = wallets.apply(&pool);
+    assert_eq!(wallets.resolve(&Address::WalletId(4)), Some(source));
+    assert_eq!(wallets.get(&source).unwrap().last_inner_id, Some(5));
+    assert_eq!(wallets.get(&target).unwrap().balance, t.amount);
+
+    assert_eq!(wallets.resolve(&Address::WalletId(8)), Some(source));
+
+    wallets.undo(undo);
+    assert_eq!(wallets.resolve(&Address::WalletId(4)), None);
+    assert_eq!(wallets.resolve(&Address::WalletId(8)), Some(target));
+    assert_eq!(wallets.get(&source), Some(&WalletData { balance: Amount::new(10, 0), last_inner_id: Some(4) }));
+    assert!(wallets.get(&target).is_none());
+}
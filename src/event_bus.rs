@@ -0,0 +1,123 @@
+use std::sync::{Arc, RwLock};
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+use super::{Hash, PublicKey};
+use super::node_state::{Role, SyncState};
+use super::core_logic::RejectReason;
+
+pub type SharedBus = Arc<EventBus>;
+
+bitflags! {
+    pub struct Topics: u32 {
+        const ROUND = 0b0000_0001;
+        const BLOCK = 0b0000_0010;
+        const PEER = 0b0000_0100;
+        const CONFIG = 0b0000_1000;
+        const SYNC = 0b0001_0000;
+        const ROLE = 0b0010_0000;
+        const NODE = 0b0100_0000;
+        const TRANSACTION = 0b1000_0000;
+
+        const ALL = Self::ROUND.bits | Self::BLOCK.bits | Self::PEER.bits | Self::CONFIG.bits | Self::SYNC.bits | Self::ROLE.bits | Self::NODE.bits | Self::TRANSACTION.bits;
+    }
+}
+
+/// Events the node subsystems publish to each other
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// new round table is handled: round
+    RoundStarted(u64),
+    /// bootstrap table is handled: round
+    BigBang(u64),
+    /// new block is stored: sequence
+    BlockStored(u64),
+    /// stored block is found to be a fork and is rolled back: sequence and hash
+    InvalidBlock(u64, Hash),
+    /// other nodes disagree with our block: sequence and hash
+    ForkSuspected(u64, Hash),
+    /// new neighbour is added
+    PeerAdded(PublicKey),
+    /// neighbour is lost
+    PeerLost(PublicKey),
+    /// configuration is reloaded and something is updated
+    ConfigChanged,
+    /// synchronization with the network is changed
+    SyncStateChanged(SyncState),
+    /// node role in the current round is changed
+    RoleChanged(Role),
+    /// authorized stop request: required node version
+    StopRequested(u16),
+    /// transaction is rejected by validation: transaction hash and reason
+    TransactionRejected(Hash, RejectReason)
+}
+
+impl Event {
+
+    pub fn topic(&self) -> Topics {
+        match self {
+            Event::RoundStarted(_) | Event::BigBang(_) => Topics::ROUND,
+            Event::BlockStored(_) | Event::InvalidBlock(_, _) | Event::ForkSuspected(_, _) => Topics::BLOCK,
+            Event::PeerAdded(_) | Event::PeerLost(_) => Topics::PEER,
+            Event::ConfigChanged => Topics::CONFIG,
+            Event::SyncStateChanged(_) => Topics::SYNC,
+            Event::RoleChanged(_) => Topics::ROLE,
+            Event::StopRequested(_) => Topics::NODE,
+            Event::TransactionRejected(_, _) => Topics::TRANSACTION
+        }
+    }
+}
+
+struct Subscriber {
+    topics: Topics,
+    tx: Sender<Event>
+}
+
+/// Publish/subscribe event bus, every subscriber receives only events of topics it is subscribed to
+pub struct EventBus {
+    subscribers: RwLock<Vec<Subscriber>>
+}
+
+impl EventBus {
+
+    pub fn new() -> EventBus {
+        EventBus {
+            subscribers: RwLock::new(Vec::<Subscriber>::new())
+        }
+    }
+
+    pub fn subscribe(&self, topics: Topics) -> Receiver<Event> {
+        let (tx, rx) = channel::<Event>();
+        let mut guard = self.subscribers.write().unwrap();
+        guard.push(Subscriber {
+            topics: topics,
+            tx: tx
+        });
+        rx
+    }
+
+    pub fn publish(&self, event: Event) {
+        let topic = event.topic();
+        let mut guard = self.subscribers.write().unwrap();
+        // drop subscribers whose receivers are gone
+        guard.retain(|s| !s.topics.contains(topic) || s.tx.send(event.clone()).is_ok());
+    }
+}
+
+#[test]
+fn test_publish_subscribe() {
+    let bus = EventBus::new();
+    let rx_round = bus.subscribe(Topics::ROUND);
+    let rx_all = bus.subscribe(Topics::ALL);
+    {
+        let _rx_dropped = bus.subscribe(Topics::ALL);
+    }
+
+    bus.publish(Event::RoundStarted(10));
+    bus.publish(Event::PeerAdded([1u8; 32]));
+
+    assert_eq!(rx_round.try_recv().unwrap(), Event::RoundStarted(10));
+    assert!(rx_round.try_recv().is_err());
+    assert_eq!(rx_all.try_recv().unwrap(), Event::RoundStarted(10));
+    assert_eq!(rx_all.try_recv().unwrap(), Event::PeerAdded([1u8; 32]));
+    assert_eq!(bus.subscribers.read().unwrap().len(), 2);
+}
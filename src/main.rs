@@ -2,7 +2,7 @@
 extern crate bitflags;
 
 extern crate log;
-use log::info;
+use log::{info, error};
 
 extern crate blake2s_simd;
 
@@ -14,6 +14,14 @@ mod network;
 use network::TEST_STOP_DELAY_SEC;
 mod collaboration;
 mod core_logic;
+mod node_state;
+use node_state::{NodeState, SharedState};
+mod event_bus;
+mod sync;
+mod csdb;
+mod storage;
+use storage::{Storage, SharedStorage};
+use event_bus::{Event, EventBus, SharedBus, Topics};
 
 use std::sync::{Arc, RwLock};
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -23,12 +31,11 @@ use std::thread::JoinHandle;
 use std::time;
 
 const NODE_VERSION: u16 = 502;
-const UUID_TESTNET: u64 = 5283967947175248524;
 pub const PUBLIC_KEY_SIZE: usize = 32;
 pub const HASH_SIZE: usize = 32;
 
 pub type PublicKey = [u8; PUBLIC_KEY_SIZE];
-//pub type Hash = [u8; HASH_SIZE]; // hash is defined in blake2s
+pub use csdb::Hash; // blake2s hash
 //static ZERO_PUBLIC_KEY: PublicKey = [0u8; PUBLIC_KEY_SIZE];
 
 fn main() {
@@ -48,25 +55,56 @@ fn main() {
 
     let stop_flag = Arc::new(AtomicBool::new(false));
     let conf: SharedConfig = Arc::new(RwLock::new(config::Config::new(&file_name)));
+    let state: SharedState = Arc::new(RwLock::new(NodeState::new()));
+    let bus: SharedBus = Arc::new(EventBus::new());
     
     // init logger
     logger::init(conf.clone());
 
+    // the node must not join unknown blockchain
+    let (uuid, network) = {
+        let conf_guard = conf.read().unwrap();
+        (conf_guard.uuid(), conf_guard.network.name)
+    };
+    if uuid == 0 {
+        error!("Network uuid of {} is unknown, set [params] network_uuid = ...", network);
+        return;
+    }
+
+    let data_dir = conf.read().unwrap().data_dir.clone();
+    let storage: SharedStorage = match Storage::open(&data_dir, state.clone(), bus.clone()) {
+        Err(e) => {
+            error!("Failed to open storage in {}: {}", data_dir, e);
+            return;
+        }
+        Ok(mut s) => {
+            storage::verify::verify_on_start(&mut s, &conf);
+            Arc::new(RwLock::new(s))
+        }
+    };
+
+    let node_events = bus.subscribe(Topics::NODE);
+
     // run config observer thread:
-    let config_observer = start_config_observer_thread(conf.clone(), stop_flag.clone());
+    let config_observer = start_config_observer_thread(conf.clone(), bus.clone(), stop_flag.clone());
     
     // run network (which in its turn will start all necessary own threads)
-    let network = start_network_thread(conf.clone(), stop_flag.clone());
+    let network = start_network_thread(conf.clone(), state.clone(), bus.clone(), storage.clone(), stop_flag.clone());
 
-    // imitate other work: sleep too long and exit
-    thread::sleep(time::Duration::from_secs(300));
+    // imitate other work: wait too long or until stop is requested and exit
+    match node_events.recv_timeout(time::Duration::from_secs(300)) {
+        Ok(Event::StopRequested(version)) => {
+            info!("Stop requested, version {} is required", version);
+        }
+        _ => ()
+    }
     stop_flag.store(true, Ordering::SeqCst);
     config_observer.join().unwrap();
     network.join().unwrap();
     info!("Node exit");
 }
 
-fn start_config_observer_thread(config: SharedConfig, stop_flag: Arc<AtomicBool>) -> JoinHandle<()> {
+fn start_config_observer_thread(config: SharedConfig, bus: SharedBus, stop_flag: Arc<AtomicBool>) -> JoinHandle<()> {
     info!("Start logger");
     let handle = spawn(move || {
         info!("Logger started");
@@ -88,19 +126,23 @@ fn start_config_observer_thread(config: SharedConfig, stop_flag: Arc<AtomicBool>
                 break;
             }
             // reload configuration parameters
+            let updated;
             {
                 let mut data_guard = config.write().unwrap();
-                data_guard.reload();
+                updated = data_guard.reload();
+            }
+            if updated {
+                bus.publish(Event::ConfigChanged);
             }
         }
     });
     handle
 }
 
-fn start_network_thread(config: SharedConfig, stop_flag: Arc<AtomicBool>) -> JoinHandle<()> {
+fn start_network_thread(config: SharedConfig, state: SharedState, bus: SharedBus, storage: SharedStorage, stop_flag: Arc<AtomicBool>) -> JoinHandle<()> {
     info!("Start network");
     let handle = spawn(move || {
-        let net = network::Network::new(config);
+        let net = network::Network::new(config, state, bus, storage);
         info!("Network started");
         loop {
             thread::sleep(time::Duration::from_secs(TEST_STOP_DELAY_SEC));
@@ -8,7 +8,9 @@ use super::TEST_STOP_DELAY_SEC;
 use super::packet::Packet;
 // top-level modules
 use super::super::config::SharedConfig;
-use super::super::collaboration::Collaboration;
+use super::super::collaboration::{Collaboration, NeighboursView};
+use super::super::node_state::SharedState;
+use super::super::event_bus::SharedBus;
 
 pub struct CommandProcessor {
     rx_cmd: Receiver<Packet>,
@@ -17,10 +19,10 @@ pub struct CommandProcessor {
 
 impl CommandProcessor {
 
-    pub fn new(conf: SharedConfig, rx_cmd: Receiver<Packet>, tx_send: Sender<Packet>) -> CommandProcessor {
+    pub fn new(conf: SharedConfig, state: SharedState, bus: SharedBus, view: NeighboursView, rx_cmd: Receiver<Packet>, tx_send: Sender<Packet>) -> CommandProcessor {
         CommandProcessor {
             rx_cmd: rx_cmd,
-            collaboration: Collaboration::new(conf, tx_send)
+            collaboration: Collaboration::new(conf, state, bus, view, tx_send)
         }
     }
 
@@ -52,4 +54,8 @@ impl CommandProcessor {
         self.collaboration.ping_all();
     }
 
+    pub fn dump_neighbours(&self) {
+        self.collaboration.dump_neighbours();
+    }
+
 }
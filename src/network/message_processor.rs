@@ -9,6 +9,10 @@ use super::packet::Packet;
 // top-level modules
 use super::super::config::SharedConfig;
 use super::super::core_logic::CoreLogic;
+use super::super::node_state::SharedState;
+use super::super::event_bus::SharedBus;
+use super::super::storage::SharedStorage;
+use super::super::collaboration::NeighboursView;
 
 pub struct MessageProcessor {
     rx_msg: Receiver<Packet>,
@@ -18,11 +22,11 @@ pub struct MessageProcessor {
 
 impl MessageProcessor {
 
-    pub fn new(conf: SharedConfig, rx_msg: Receiver<Packet>, tx_send:Sender<Packet>) -> MessageProcessor {
+    pub fn new(conf: SharedConfig, state: SharedState, bus: SharedBus, storage: SharedStorage, view: NeighboursView, rx_msg: Receiver<Packet>, tx_send:Sender<Packet>) -> MessageProcessor {
         MessageProcessor {
             rx_msg: rx_msg,
             tx_send: tx_send.clone(),
-            logic: CoreLogic::new(conf, tx_send)
+            logic: CoreLogic::new(conf, state, bus, storage, view, tx_send)
         }
     }
 
@@ -55,6 +59,7 @@ impl MessageProcessor {
                 }
             }
         }
+        self.logic.on_timer();
     }
 
 }
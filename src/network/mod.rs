@@ -1,4 +1,8 @@
-use super::config::SharedConfig;
+use super::config::{SharedConfig, Profile};
+use super::node_state::SharedState;
+use super::event_bus::SharedBus;
+use super::storage::SharedStorage;
+use super::collaboration::NeighboursView;
 use std::thread::{JoinHandle, spawn};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -43,7 +47,7 @@ pub struct Network {
 }
 
 impl Network {
-	pub fn new(conf: SharedConfig) -> Box<Network> {
+	pub fn new(conf: SharedConfig, state: SharedState, bus: SharedBus, storage: SharedStorage) -> Box<Network> {
 		let stop_flag_instance = Arc::new(AtomicBool::new(false));
         // p2p-compat -> packet_collector channel, fully async:
         let (tx_raw, rx_raw) = channel::<RawPacket>();
@@ -59,10 +63,12 @@ impl Network {
 		// get from config
 		let node_id: String;
 		let hosts_filename: String;
+		let profile: &'static Profile;
 		{
 			let conf_guard = conf.read().unwrap();
 			node_id = conf_guard.node_id.clone();
 			hosts_filename = conf_guard.hosts_filename.clone();
+			profile = conf_guard.network;
 		}
 	
 		// init host with own id
@@ -71,16 +77,18 @@ impl Network {
 	
 		// init host entry points list
 		let mut known_hosts = Vec::<NodeInfo>::new();
-		parse_known_hosts_or_default(&mut known_hosts, &hosts_filename);
+		parse_known_hosts_or_default(&mut known_hosts, &hosts_filename, profile);
 		host.add_known_hosts(known_hosts);
 		host.start();
+
+		let neighbours = NeighboursView::new(state.clone());
 		
 		let instance = Box::new(
             Network {
                 stop_flag: stop_flag_instance.clone(),
                 collect_thread: start_collect(conf.clone(), stop_flag_instance.clone(), rx_raw, tx_cmd, tx_msg),
-                neighbours_thread: start_neighbourhood(conf.clone(), stop_flag_instance.clone(), rx_cmd, tx_send.clone()),
-                processor_thread: start_msg_processor(conf.clone(), stop_flag_instance.clone(), rx_msg, tx_send),
+                neighbours_thread: start_neighbourhood(conf.clone(), state.clone(), bus.clone(), neighbours.clone(), stop_flag_instance.clone(), rx_cmd, tx_send.clone()),
+                processor_thread: start_msg_processor(conf.clone(), state, bus, storage, neighbours, stop_flag_instance.clone(), rx_msg, tx_send),
                 sender_thread: start_sender(conf.clone(), stop_flag_instance.clone(), rx_send),
                 host: host
             });
@@ -116,18 +124,24 @@ fn start_collect(_conf: SharedConfig, stop_flag: Arc<AtomicBool>,
 	handle
 }
 
-fn start_neighbourhood(conf: SharedConfig, stop_flag: Arc<AtomicBool>, rx_cmd: Receiver<Packet>, tx_send: Sender<Packet>) -> JoinHandle<()> {
+fn start_neighbourhood(conf: SharedConfig, state: SharedState, bus: SharedBus, view: NeighboursView, stop_flag: Arc<AtomicBool>, rx_cmd: Receiver<Packet>, tx_send: Sender<Packet>) -> JoinHandle<()> {
 	info!("Start neighbourhood service");
 	let handle = spawn(move || {
         info!("Neighbourhood started");
-        let mut neighbourhood = command_processor::CommandProcessor::new(conf.clone(), rx_cmd, tx_send);
+        let mut neighbourhood = command_processor::CommandProcessor::new(conf.clone(), state, bus, view, rx_cmd, tx_send);
         let mut prev_ping = Instant::now();
+        let mut prev_dump = Instant::now();
         loop {
             let ping_pause = prev_ping.elapsed();
             if ping_pause.as_millis() as u64 >= PING_NEIGHBOURS_DELAY_MS {
                 neighbourhood.ping_all();
                 prev_ping = Instant::now();
             }
+            let dump_delay_sec = conf.read().unwrap().neighbours_dump_sec;
+            if dump_delay_sec > 0 && prev_dump.elapsed().as_secs() >= dump_delay_sec {
+                neighbourhood.dump_neighbours();
+                prev_dump = Instant::now();
+            }
             neighbourhood.recv();
             if stop_flag.load(Ordering::SeqCst) {
                 break;
@@ -138,11 +152,11 @@ fn start_neighbourhood(conf: SharedConfig, stop_flag: Arc<AtomicBool>, rx_cmd: R
 	handle
 }
 
-fn start_msg_processor(_conf: SharedConfig, stop_flag: Arc<AtomicBool>, rx_msg: Receiver<Packet>, tx_send: Sender<Packet>) -> JoinHandle<()> {
+fn start_msg_processor(_conf: SharedConfig, state: SharedState, bus: SharedBus, storage: SharedStorage, view: NeighboursView, stop_flag: Arc<AtomicBool>, rx_msg: Receiver<Packet>, tx_send: Sender<Packet>) -> JoinHandle<()> {
 	info!("Start message processor");
 	let handle = spawn(move || {
         info!("Message processor started");
-        let mut msg_processor = message_processor::MessageProcessor::new(_conf.clone(), rx_msg, tx_send);
+        let mut msg_processor = message_processor::MessageProcessor::new(_conf.clone(), state, bus, storage, view, rx_msg, tx_send);
         loop {
             msg_processor.recv();
             if stop_flag.load(Ordering::SeqCst) {
@@ -170,19 +184,11 @@ fn start_sender(_conf: SharedConfig, stop_flag: Arc<AtomicBool>, rx_send: Receiv
 	handle
 }
 
-fn parse_known_hosts_or_default(known_hosts: &mut Vec<NodeInfo>, hosts_filename: &String) {
+fn parse_known_hosts_or_default(known_hosts: &mut Vec<NodeInfo>, hosts_filename: &String, profile: &Profile) {
     if hosts_filename.len() > 0 {
         match File::open(PathBuf::from(&hosts_filename)) {
             Err(e) => {
                 println!("Failed to open file {}: {}", &hosts_filename, e);
-                // add well known ru3 as entry point
-                known_hosts.push(
-                    NodeInfo {
-                        id: "HBxj19cnpayn46GSqBGyKQXMaLThH4quuPt5gf8aFndg".from_base58().unwrap(),
-                        ip: "195.133.147.58".to_string(),
-                        port: 9000
-                    }
-                );
             }
             Ok(f) => {
                 let reader = BufReader::new(f);
@@ -195,30 +201,45 @@ fn parse_known_hosts_or_default(known_hosts: &mut Vec<NodeInfo>, hosts_filename:
                                 println!("Malformed known_hosts record, must conform <ip:port id>, found {}", item);
                                 continue;
                             }
-                            let addr = parts[0].split(':').collect::<Vec<_>>();
-                            if addr.len() != 2 {
-                                println!("Malformed ip:port part, found {}", parts[0]);
-                                continue;
+                            if let Some(info) = parse_known_host(parts[0], parts[1]) {
+                                known_hosts.push(info);
                             }
-                            // base58 -> Vec<u8>
-                            let bytes: Vec<u8> = match parts[1].from_base58() {
-                                Err(_) => {
-                                    println!("Malformed id, must be a 32-byte key encoded base58, found {}", parts[1]);
-                                    continue;
-                                }
-                                Ok(b) => b
-                            };
-                            known_hosts.push(
-                                NodeInfo {
-                                    id: bytes,
-                                    ip: addr[0].to_string(),
-                                    port: u16::from_str(addr[1]).unwrap_or(0)
-                                }
-                            );
                         }
                     }
                 }
             }
         }
     }
+    if known_hosts.is_empty() {
+        // add well known entry points of the selected network
+        println!("Use default known hosts of {}", profile.name);
+        for (addr, id) in profile.known_hosts {
+            if let Some(info) = parse_known_host(addr, id) {
+                known_hosts.push(info);
+            }
+        }
+    }
+}
+
+fn parse_known_host(ip_port: &str, id: &str) -> Option<NodeInfo> {
+    let addr = ip_port.split(':').collect::<Vec<_>>();
+    if addr.len() != 2 {
+        println!("Malformed ip:port part, found {}", ip_port);
+        return None;
+    }
+    // base58 -> Vec<u8>
+    let bytes: Vec<u8> = match id.from_base58() {
+        Err(_) => {
+            println!("Malformed id, must be a 32-byte key encoded base58, found {}", id);
+            return None;
+        }
+        Ok(b) => b
+    };
+    Some(
+        NodeInfo {
+            id: bytes,
+            ip: addr[0].to_string(),
+            port: u16::from_str(addr[1]).unwrap_or(0)
+        }
+    )
 }
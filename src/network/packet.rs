@@ -140,6 +140,20 @@ impl Packet {
 		})
 	}
 
+	/// builds message pack: flags(1) + msg(1) + round(8) + payload, broadcast if target is not set
+	pub fn new_message(target: Option<&PublicKey>, msg: MsgType, round: u64, payload: &[u8]) -> Option<Packet> {
+		let mut bytes = Vec::<u8>::with_capacity(1 + 1 + 8 + payload.len());
+		bytes.push(Flags::ZERO.bits());
+		bytes.push(msg as u8);
+		bytes.extend_from_slice(&round.to_le_bytes());
+		bytes.extend_from_slice(payload);
+		let mut pack = Packet::new_broadcast(bytes)?;
+		if let Some(id) = target {
+			pack.set_address(id);
+		}
+		Some(pack)
+	}
+
 	pub fn is_message(&self) -> bool {
 		! self.is_neigbour()
 	}
@@ -0,0 +1,70 @@
+use std::sync::{Arc, RwLock};
+use std::fmt;
+
+pub type SharedState = Arc<RwLock<NodeState>>;
+
+/// Role of the node in the current round
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Role {
+    /// does not take part in consensus
+    Normal,
+    /// confidant, takes part in consensus
+    Trusted,
+    /// confidant who writes the block of the round
+    Writer
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// Synchronization of the node with the network
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SyncState {
+    /// stored sequence and current round are up to date
+    Synced,
+    /// lag behind the neighbours is detected
+    Behind,
+    /// block synchronization is in progress
+    Syncing,
+    /// block synchronization makes no progress
+    Stalled
+}
+
+impl fmt::Display for SyncState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// The node state shared between subsystems: CoreLogic updates it, others read
+pub struct NodeState {
+    /// the last stored block sequence
+    pub sequence: u64,
+    /// the current consensus round
+    pub round: u64,
+    /// the role of the node in the current round
+    pub role: Role,
+    /// max stored sequence reported by neighbours
+    pub network_sequence: u64,
+    /// max round reported by neighbours
+    pub network_round: u64,
+    /// synchronization with the network
+    pub sync: SyncState
+}
+
+impl NodeState {
+
+    pub fn new() -> NodeState {
+        NodeState {
+            sequence: 0,
+            round: 0,
+            role: Role::Normal,
+            network_sequence: 0,
+            network_round: 0,
+            sync: SyncState::Synced
+        }
+    }
+}
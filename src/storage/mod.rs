@@ -0,0 +1,392 @@
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+
+use log::{info, warn};
+
+use super::{Hash, HASH_SIZE};
+use super::csdb::Pool;
+use super::node_state::SharedState;
+use super::event_bus::{Event, SharedBus};
+
+pub mod verify;
+
+pub type SharedStorage = Arc<RwLock<Storage>>;
+
+/// subdirectory of data_dir the blockchain is stored in
+const STORAGE_DIR: &str = "blocks";
+const LOG_FILE: &str = "blocks.dat";
+const INDEX_FILE: &str = "blocks.idx";
+/// index entry: sequence(8) + offset(8) + hash(32)
+const INDEX_ENTRY_SIZE: u64 = 8 + 8 + HASH_SIZE as u64;
+
+struct IndexEntry {
+    /// offset of the record in log
+    offset: u64,
+    hash: Hash
+}
+
+/// Append-only block log with sequence -> offset and hash -> sequence indexes.
+/// Log record: size(4) + pool bytes + size(4), the trailing size marks the record is completely written.
+pub struct Storage {
+    dir: PathBuf,
+    log: File,
+    /// separate handle to read log under shared lock
+    reader: Mutex<File>,
+    log_len: u64,
+    index: File,
+    /// sequence of the first stored block
+    first: u64,
+    /// entries[seq - first]
+    entries: Vec<IndexEntry>,
+    hashes: HashMap<Hash, u64>,
+    state: SharedState,
+    bus: SharedBus
+}
+
+impl Storage {
+
+    /// opens storage in data_dir, recovers it after crash if required
+    pub fn open(data_dir: &str, state: SharedState, bus: SharedBus) -> io::Result<Storage> {
+        let mut dir = PathBuf::from(data_dir);
+        dir.push(STORAGE_DIR);
+        fs::create_dir_all(&dir)?;
+        let log = open_file(&dir.join(LOG_FILE))?;
+        let reader = File::open(dir.join(LOG_FILE))?;
+        let index = open_file(&dir.join(INDEX_FILE))?;
+        let log_len = log.metadata()?.len();
+        let mut storage = Storage {
+            dir: dir,
+            log: log,
+            reader: Mutex::new(reader),
+            log_len: log_len,
+            index: index,
+            first: 0,
+            entries: Vec::new(),
+            hashes: HashMap::new(),
+            state: state,
+            bus: bus
+        };
+        storage.recover()?;
+        if let Some(last) = storage.last_sequence() {
+            info!("storage {}: blocks {}..{}, {} bytes", storage.dir.display(), storage.first, last, storage.log_len);
+            storage.state.write().unwrap().sequence = last;
+        }
+        else {
+            info!("storage {} is empty", storage.dir.display());
+        }
+        Ok(storage)
+    }
+
+    #[cfg(test)]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn first_sequence(&self) -> Option<u64> {
+        if self.entries.is_empty() { None } else { Some(self.first) }
+    }
+
+    pub fn last_sequence(&self) -> Option<u64> {
+        if self.entries.is_empty() { None } else { Some(self.first + self.entries.len() as u64 - 1) }
+    }
+
+    pub fn last_hash(&self) -> Option<Hash> {
+        self.entries.last().map(|e| e.hash)
+    }
+
+    pub fn hash_of(&self, sequence: u64) -> Option<Hash> {
+        self.entry(sequence).map(|e| e.hash)
+    }
+
+    pub fn sequence_of(&self, hash: &Hash) -> Option<u64> {
+        self.hashes.get(hash).cloned()
+    }
+
+    /// stored pool binary by sequence
+    pub fn get_bytes(&self, sequence: u64) -> io::Result<Option<Vec<u8>>> {
+        let offset = match self.entry(sequence) {
+            None => return Ok(None),
+            Some(e) => e.offset
+        };
+        let mut reader = self.reader.lock().unwrap();
+        read_record(&mut *reader, offset, self.log_len).map(Some)
+    }
+
+    pub fn get(&self, sequence: u64) -> io::Result<Option<Pool>> {
+        match self.get_bytes(sequence)? {
+            None => Ok(None),
+            Some(bytes) => Pool::from_bytes(&bytes)
+                .map(|(pool, _)| Some(pool))
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+        }
+    }
+
+    /// appends the next pool, it is durable on return
+    pub fn store(&mut self, pool: &Pool) -> io::Result<Hash> {
+        if let Some(last) = self.last_sequence() {
+            if pool.sequence != last + 1 {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                    format!("block {} does not follow the last stored {}", pool.sequence, last)));
+            }
+        }
+        let bytes = pool.to_bytes();
+        let hash = pool.hash();
+        let offset = self.log_len;
+        if let Err(e) = self.append_record(offset, &bytes) {
+            // drop partially written record
+            let _ = self.log.set_len(offset);
+            return Err(e);
+        }
+        self.log_len = offset + record_size(bytes.len());
+        if self.entries.is_empty() {
+            self.first = pool.sequence;
+        }
+        self.push_entry(pool.sequence, offset, hash, true)?;
+
+        self.state.write().unwrap().sequence = pool.sequence;
+        self.bus.publish(Event::BlockStored(pool.sequence));
+        Ok(hash)
+    }
+
+    /// drops blocks starting with sequence
+    pub fn truncate_from(&mut self, sequence: u64) -> io::Result<()> {
+        let keep = if sequence < self.first { 0 } else { (sequence - self.first) as usize };
+        if keep >= self.entries.len() {
+            return Ok(());
+        }
+        let offset = self.entries[keep].offset;
+        for e in self.entries.drain(keep..) {
+            self.hashes.remove(&e.hash);
+        }
+        // log first: index entries beyond the log are dropped by recovery, not vice versa
+        self.log.set_len(offset)?;
+        self.log.sync_data()?;
+        self.log_len = offset;
+        self.index.set_len(keep as u64 * INDEX_ENTRY_SIZE)?;
+        self.index.sync_data()?;
+        warn!("storage is truncated to {} bytes, the last block is {:?}", offset, self.last_sequence());
+        self.state.write().unwrap().sequence = self.last_sequence().unwrap_or(0);
+        Ok(())
+    }
+
+    fn entry(&self, sequence: u64) -> Option<&IndexEntry> {
+        if sequence < self.first {
+            return None;
+        }
+        self.entries.get((sequence - self.first) as usize)
+    }
+
+    fn append_record(&mut self, offset: u64, bytes: &[u8]) -> io::Result<()> {
+        let size = (bytes.len() as u32).to_le_bytes();
+        self.log.seek(SeekFrom::Start(offset))?;
+        self.log.write_all(&size)?;
+        self.log.write_all(bytes)?;
+        self.log.write_all(&size)?;
+        self.log.sync_data()
+    }
+
+    fn push_entry(&mut self, sequence: u64, offset: u64, hash: Hash, write: bool) -> io::Result<()> {
+        if write {
+            let mut output = Vec::<u8>::with_capacity(INDEX_ENTRY_SIZE as usize);
+            output.extend_from_slice(&sequence.to_le_bytes());
+            output.extend_from_slice(&offset.to_le_bytes());
+            output.extend_from_slice(hash.as_bytes());
+            self.index.seek(SeekFrom::Start(self.entries.len() as u64 * INDEX_ENTRY_SIZE))?;
+            self.index.write_all(&output)?;
+            self.index.sync_data()?;
+        }
+        self.hashes.insert(hash, sequence);
+        self.entries.push(IndexEntry {
+            offset: offset,
+            hash: hash
+        });
+        Ok(())
+    }
+
+    /// loads index entries consistent with log, indexes log records missing in index,
+    /// truncates partial tails of both files
+    fn recover(&mut self) -> io::Result<()> {
+        let mut index_bytes = Vec::<u8>::new();
+        self.index.seek(SeekFrom::Start(0))?;
+        self.index.read_to_end(&mut index_bytes)?;
+
+        let mut end = 0u64;
+        let mut reader = self.log.try_clone()?;
+        for chunk in index_bytes.chunks(INDEX_ENTRY_SIZE as usize) {
+            if chunk.len() < INDEX_ENTRY_SIZE as usize {
+                break;
+            }
+            let sequence = u64::from_le_bytes(chunk[..8].try_into().unwrap());
+            let offset = u64::from_le_bytes(chunk[8..16].try_into().unwrap());
+            let hash = Hash::from_slice(&chunk[16..]).unwrap();
+            let expected = self.last_sequence().map(|s| s + 1).unwrap_or(sequence);
+            if sequence != expected || offset != end {
+                warn!("storage index is inconsistent at block {}, rebuild the rest", sequence);
+                break;
+            }
+            match read_record_size(&mut reader, offset, self.log_len) {
+                Err(_) => {
+                    warn!("storage index refers block {} beyond the log, rebuild the rest", sequence);
+                    break;
+                }
+                Ok(size) => end = offset + record_size(size)
+            }
+            if self.entries.is_empty() {
+                self.first = sequence;
+            }
+            self.push_entry(sequence, offset, hash, false)?;
+        }
+        let indexed = self.entries.len();
+        if index_bytes.len() as u64 != indexed as u64 * INDEX_ENTRY_SIZE {
+            self.index.set_len(indexed as u64 * INDEX_ENTRY_SIZE)?;
+        }
+
+        // index log records written after the last index entry
+        while end < self.log_len {
+            let bytes = match read_record(&mut reader, end, self.log_len) {
+                Err(_) => break,
+                Ok(v) => v
+            };
+            let (pool, hash) = match Pool::from_bytes(&bytes) {
+                Err(_) => break,
+                Ok(v) => v
+            };
+            if let Some(last) = self.last_sequence() {
+                if pool.sequence != last + 1 {
+                    break;
+                }
+            }
+            else {
+                self.first = pool.sequence;
+            }
+            self.push_entry(pool.sequence, end, hash, true)?;
+            end += record_size(bytes.len());
+        }
+        if self.entries.len() > indexed {
+            info!("storage: {} blocks are indexed from log", self.entries.len() - indexed);
+        }
+
+        if end < self.log_len {
+            warn!("storage: partial tail of {} bytes is truncated", self.log_len - end);
+            self.log.set_len(end)?;
+            self.log.sync_data()?;
+            self.log_len = end;
+        }
+        Ok(())
+    }
+}
+
+fn open_file(path: &Path) -> io::Result<File> {
+    OpenOptions::new().read(true).write(true).create(true).open(path)
+}
+
+fn record_size(size: usize) -> u64 {
+    4 + size as u64 + 4
+}
+
+/// reads record header at offset, returns size of the pool bytes
+fn read_record_size(reader: &mut File, offset: u64, log_len: u64) -> io::Result<usize> {
+    let mut size = [0u8; 4];
+    reader.seek(SeekFrom::Start(offset))?;
+    reader.read_exact(&mut size)?;
+    let size = u32::from_le_bytes(size) as usize;
+    if offset + record_size(size) > log_len {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "partial record"));
+    }
+    Ok(size)
+}
+
+/// reads complete record at offset, returns the pool bytes
+fn read_record(reader: &mut File, offset: u64, log_len: u64) -> io::Result<Vec<u8>> {
+    let size = read_record_size(reader, offset, log_len)?;
+    let mut bytes = vec![0u8; size + 4];
+    reader.read_exact(&mut bytes)?;
+    if bytes[size..] != (size as u32).to_le_bytes() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "record trailer mismatch"));
+    }
+    bytes.truncate(size);
+    Ok(bytes)
+}
+
+/// temporary directory of a test, removed on drop even if the test panics
+#[cfg(test)]
+pub struct TestDir(PathBuf);
+
+#[cfg(test)]
+impl TestDir {
+
+    pub fn new(name: &str) -> TestDir {
+        let mut dir = std::env::temp_dir();
+        dir.push(format!("node-rs-test-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        TestDir(dir)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// empty storage in the test directory, keep the directory until the storage is not used
+#[cfg(test)]
+pub fn test_storage(name: &str) -> (Storage, TestDir) {
+    use super::node_state::NodeState;
+    use super::event_bus::EventBus;
+
+    let dir = TestDir::new(name);
+    let storage = Storage::open(dir.path().to_str().unwrap(), Arc::new(RwLock::new(NodeState::new())), Arc::new(EventBus::new())).unwrap();
+    (storage, dir)
+}
+
+#[test]
+fn test_storage_store_and_recover() {
+    use super::csdb::test_pool;
+
+    let (mut storage, _dir) = test_storage("storage");
+    let mut prev = Hash::default();
+    for seq in 0..3 {
+        prev = storage.store(&test_pool(prev, seq)).unwrap();
+    }
+    assert!(storage.store(&test_pool(prev, 5)).is_err());
+    assert_eq!(storage.last_sequence(), Some(2));
+    assert_eq!(storage.last_hash(), Some(prev));
+    assert_eq!(storage.sequence_of(&prev), Some(2));
+    assert_eq!(storage.get(1).unwrap().unwrap().sequence, 1);
+    assert!(storage.get(3).unwrap().is_none());
+    assert_eq!(storage.state.read().unwrap().sequence, 2);
+
+    // crash: partial tail in log, the last index entry is lost
+    let dir = storage.dir().to_path_buf();
+    let data_dir = dir.parent().unwrap().to_str().unwrap().to_string();
+    let (state, bus) = (storage.state.clone(), storage.bus.clone());
+    drop(storage);
+    let mut log = OpenOptions::new().append(true).open(dir.join(LOG_FILE)).unwrap();
+    log.write_all(&[100u8, 0, 0, 0, 1, 2, 3]).unwrap();
+    let index = OpenOptions::new().write(true).open(dir.join(INDEX_FILE)).unwrap();
+    index.set_len(2 * INDEX_ENTRY_SIZE - 5).unwrap();
+
+    let storage = Storage::open(&data_dir, state, bus).unwrap();
+    assert_eq!(storage.last_sequence(), Some(2));
+    assert_eq!(storage.last_hash(), Some(prev));
+    assert_eq!(fs::metadata(dir.join(INDEX_FILE)).unwrap().len(), 3 * INDEX_ENTRY_SIZE);
+    assert_eq!(storage.get(2).unwrap().unwrap().previous_hash, storage.hash_of(1).unwrap());
+
+    let mut storage = storage;
+    storage.truncate_from(1).unwrap();
+    assert_eq!(storage.last_sequence(), Some(0));
+    assert!(storage.sequence_of(&prev).is_none());
+    assert_eq!(storage.state.read().unwrap().sequence, 0);
+    let hash = storage.store(&test_pool(storage.last_hash().unwrap(), 1)).unwrap();
+    assert_eq!(storage.sequence_of(&hash), Some(1));
+}
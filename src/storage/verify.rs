@@ -0,0 +1,233 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use log::{error, info, warn};
+
+use super::Storage;
+use super::super::Hash;
+use super::super::csdb::Pool;
+use super::super::config::SharedConfig;
+
+extern crate ed25519_dalek;
+use ed25519_dalek::Verifier;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VerifyMode {
+    Off,
+    /// the last blocks only
+    Fast,
+    Full
+}
+
+impl VerifyMode {
+
+    pub fn from_name(name: &str) -> Option<VerifyMode> {
+        match name {
+            "off" => Some(VerifyMode::Off),
+            "fast" => Some(VerifyMode::Fast),
+            "full" => Some(VerifyMode::Full),
+            _ => None
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VerifyError {
+    /// block cannot be read or decoded
+    Unreadable(String),
+    /// stored sequence differs from the expected one
+    Sequence(u64),
+    /// hash of block differs from the indexed one
+    Hash(Hash),
+    /// previous hash does not refer the previous block
+    PreviousHash(Hash),
+    /// signature of confidant is wrong, repeated or the confidant is not real trusted
+    Signature(u8),
+    /// count of signers is not the majority of confidants
+    Signers(u32)
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VerifyError::Unreadable(e) => write!(f, "unreadable: {}", e),
+            VerifyError::Sequence(s) => write!(f, "sequence {} is stored", s),
+            VerifyError::Hash(h) => write!(f, "actual hash {}", h),
+            VerifyError::PreviousHash(h) => write!(f, "previous hash {} is not of the previous block", h),
+            VerifyError::Signature(i) => write!(f, "wrong signature of confidant [{}]", i),
+            VerifyError::Signers(n) => write!(f, "signed by {} confidants only", n)
+        }
+    }
+}
+
+/// the first bad block found
+#[derive(Debug, Clone, PartialEq)]
+pub struct BadBlock {
+    pub sequence: u64,
+    pub error: VerifyError
+}
+
+impl Storage {
+
+    /// walks stored blocks up from the first (full) or the tail_blocks last (fast) ones,
+    /// the last block is verified at least; returns the first bad block if any
+    pub fn verify(&self, mode: VerifyMode, tail_blocks: u64) -> Option<BadBlock> {
+        let (first, last) = match (self.first_sequence(), self.last_sequence()) {
+            (Some(f), Some(l)) => (f, l),
+            _ => return None
+        };
+        let start = match mode {
+            VerifyMode::Off => return None,
+            VerifyMode::Fast => std::cmp::max(first, (last + 1).saturating_sub(std::cmp::max(1, tail_blocks))),
+            VerifyMode::Full => first
+        };
+        for sequence in start..=last {
+            if let Err(e) = self.verify_block(sequence, first) {
+                return Some(BadBlock {
+                    sequence: sequence,
+                    error: e
+                });
+            }
+        }
+        None
+    }
+
+    fn verify_block(&self, sequence: u64, first: u64) -> Result<(), VerifyError> {
+        let bytes = match self.get_bytes(sequence) {
+            Ok(Some(v)) => v,
+            Ok(None) => return Err(VerifyError::Unreadable("not indexed".to_string())),
+            Err(e) => return Err(VerifyError::Unreadable(e.to_string()))
+        };
+        let (pool, hash) = Pool::from_bytes(&bytes).map_err(|e| VerifyError::Unreadable(e.to_string()))?;
+        if pool.sequence != sequence {
+            return Err(VerifyError::Sequence(pool.sequence));
+        }
+        if Some(hash) != self.hash_of(sequence) {
+            return Err(VerifyError::Hash(hash));
+        }
+        // the previous block of the first stored one is not known
+        if sequence > first && Some(pool.previous_hash) != self.hash_of(sequence - 1) {
+            return Err(VerifyError::PreviousHash(pool.previous_hash));
+        }
+        verify_signatures(&pool, &hash)
+    }
+}
+
+/// the majority of confidants sign the pool hash, every signer is marked in real trusted mask;
+/// confidants are taken from the pool itself, so it proves the pool is consistent,
+/// not that its confidants are trusted nodes of the round, test that against the round table
+pub fn verify_signatures(pool: &Pool, hash: &Hash) -> Result<(), VerifyError> {
+    let mut signed: u64 = 0;
+    for (index, signature) in &pool.signatures {
+        let bit = 1u64.checked_shl(*index as u32).unwrap_or(0);
+        if pool.real_trusted & bit == 0 || signed & bit != 0 {
+            return Err(VerifyError::Signature(*index));
+        }
+        signed |= bit;
+        let key = pool.confidants.get(*index as usize)
+            .and_then(|k| ed25519_dalek::PublicKey::from_bytes(k).ok())
+            .ok_or(VerifyError::Signature(*index))?;
+        let signature = ed25519_dalek::Signature::try_from(&signature[..]).map_err(|_| VerifyError::Signature(*index))?;
+        key.verify(hash.as_bytes(), &signature).map_err(|_| VerifyError::Signature(*index))?;
+    }
+    let signers = signed.count_ones();
+    if signers as usize * 2 <= pool.confidants.len() {
+        return Err(VerifyError::Signers(signers));
+    }
+    Ok(())
+}
+
+/// verifies stored blocks as configured, truncates them to the last good one if allowed
+pub fn verify_on_start(storage: &mut Storage, conf: &SharedConfig) {
+    let mode_name;
+    let tail_blocks;
+    let truncate;
+    {
+        let conf_guard = conf.read().unwrap();
+        mode_name = conf_guard.verify_blocks.clone();
+        tail_blocks = conf_guard.verify_tail_blocks;
+        truncate = conf_guard.verify_truncate;
+    }
+    let mode = match VerifyMode::from_name(&mode_name) {
+        None => {
+            warn!("unknown verify_blocks value {}, must be off, fast or full; use fast", mode_name);
+            VerifyMode::Fast
+        }
+        Some(m) => m
+    };
+    if mode == VerifyMode::Off {
+        return;
+    }
+    info!("verify stored blocks, {:?} mode", mode);
+    match storage.verify(mode, tail_blocks) {
+        None => {
+            info!("stored blocks are verified, the last is {:?}", storage.last_sequence());
+        }
+        Some(bad) => {
+            error!("stored block {} is bad: {}", bad.sequence, bad.error);
+            if !truncate {
+                return;
+            }
+            match storage.truncate_from(bad.sequence) {
+                Err(e) => {
+                    error!("failed to truncate stored blocks: {}", e);
+                }
+                Ok(_) => {
+                    info!("stored blocks are truncated to the last good {:?}", storage.last_sequence());
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_verify() {
+    use ed25519_dalek::{ExpandedSecretKey, SecretKey};
+    use super::super::csdb::test_pool;
+
+    let secret = SecretKey::from_bytes(&[9u8; 32]).unwrap();
+    let public = ed25519_dalek::PublicKey::from(&secret);
+    let signed_pool = |previous_hash: Hash, sequence: u64| {
+        let mut pool = test_pool(previous_hash, sequence);
+        pool.confidants = vec![public.to_bytes()];
+        pool.real_trusted = 1;
+        let hash = pool.hash();
+        pool.signatures = vec![(0, ExpandedSecretKey::from(&secret).sign(hash.as_bytes(), &public).to_bytes())];
+        pool
+    };
+
+    let (mut storage, _dir) = super::test_storage("verify");
+    let mut prev = Hash::default();
+    for seq in 0..5 {
+        prev = storage.store(&signed_pool(prev, seq)).unwrap();
+    }
+    assert_eq!(storage.verify(VerifyMode::Full, 0), None);
+
+    // broken link and bad signature
+    storage.store(&signed_pool(Hash::default(), 5)).unwrap();
+    let mut pool = signed_pool(storage.last_hash().unwrap(), 6);
+    pool.signatures[0].1[0] ^= 1;
+    storage.store(&pool).unwrap();
+    let bad = storage.verify(VerifyMode::Fast, 1).unwrap();
+    assert_eq!(bad, BadBlock { sequence: 6, error: VerifyError::Signature(0) });
+    assert_eq!(storage.verify(VerifyMode::Fast, 0), Some(bad));
+    let bad = storage.verify(VerifyMode::Full, 0).unwrap();
+    assert_eq!(bad.sequence, 5);
+    assert_eq!(bad.error, VerifyError::PreviousHash(Hash::default()));
+
+    // no signatures, repeated signer and signer out of real trusted
+    let mut pool = signed_pool(prev, 5);
+    let hash = pool.hash();
+    let signature = pool.signatures[0];
+    pool.signatures.clear();
+    assert_eq!(verify_signatures(&pool, &hash), Err(VerifyError::Signers(0)));
+    pool.signatures = vec![signature, signature];
+    assert_eq!(verify_signatures(&pool, &hash), Err(VerifyError::Signature(0)));
+    pool.signatures = vec![signature];
+    pool.real_trusted = 0b10;
+    assert_eq!(verify_signatures(&pool, &hash), Err(VerifyError::Signature(0)));
+
+    storage.truncate_from(bad.sequence).unwrap();
+    assert_eq!(storage.verify(VerifyMode::Full, 0), None);
+    assert_eq!(storage.last_hash(), Some(prev));
+}
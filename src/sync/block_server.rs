@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use log::{debug, warn};
+
+use super::super::PublicKey;
+use super::super::storage::Storage;
+use super::pool_sync::pack_blocks;
+
+extern crate base58;
+use base58::ToBase58;
+
+/// max size of one reply payload
+const MAX_REPLY_BYTES: usize = 1024 * 1024;
+/// pack_blocks() framing: blocks count(4) + packet number(8) per reply, size(4) per block
+const REPLY_HEADER_BYTES: usize = 4 + 8;
+const BLOCK_HEADER_BYTES: usize = 4;
+/// max count of blocks to serve one peer during RATE_WINDOW
+const MAX_BLOCKS_PER_WINDOW: usize = 100;
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+struct Quota {
+    window_start: Instant,
+    served: usize
+}
+
+/// Serves BlockRequests from storage, rate-limited per requesting peer
+pub struct BlockServer {
+    quotas: HashMap<PublicKey, Quota>
+}
+
+impl BlockServer {
+
+    pub fn new() -> BlockServer {
+        BlockServer {
+            quotas: HashMap::new()
+        }
+    }
+
+    /// payloads of RequestedBlock replies to the peer: one block per reply if single_block_reply,
+    /// otherwise blocks are batched up to MAX_REPLY_BYTES; blocks not fitting a reply are skipped,
+    /// blocks beyond peer's quota are not served, missing ones do not count against it
+    pub fn replies(&mut self, storage: &Storage, peer: &PublicKey, sequences: &[u64], packet: u64, single_block_reply: bool) -> Vec<Vec<u8>> {
+        let allowed = self.quota(peer);
+        let mut served = 0;
+        let mut replies = Vec::<Vec<u8>>::new();
+        let mut batch = Vec::<Vec<u8>>::new();
+        let mut batch_bytes = REPLY_HEADER_BYTES;
+        for (i, seq) in sequences.iter().enumerate() {
+            if served == allowed {
+                debug!("{} of {} requested blocks are not served to {}: rate limit", sequences.len() - i, sequences.len(), peer.to_base58());
+                break;
+            }
+            let bytes = match storage.get_bytes(*seq) {
+                Err(e) => {
+                    warn!("failed to read block {}: {}", seq, e);
+                    continue;
+                }
+                Ok(None) => continue,
+                Ok(Some(v)) => v
+            };
+            let size = BLOCK_HEADER_BYTES + bytes.len();
+            if REPLY_HEADER_BYTES + size > MAX_REPLY_BYTES {
+                warn!("block {} of {} bytes exceeds reply limit, not served", seq, bytes.len());
+                continue;
+            }
+            if !batch.is_empty() && (single_block_reply || batch_bytes + size > MAX_REPLY_BYTES) {
+                replies.push(pack_blocks(&batch, packet));
+                batch.clear();
+                batch_bytes = REPLY_HEADER_BYTES;
+            }
+            batch_bytes += size;
+            batch.push(bytes);
+            served += 1;
+        }
+        if !batch.is_empty() {
+            replies.push(pack_blocks(&batch, packet));
+        }
+        if let Some(q) = self.quotas.get_mut(peer) {
+            q.served += served;
+        }
+        replies
+    }
+
+    /// count of blocks the peer may be served yet in the current window
+    fn quota(&mut self, peer: &PublicKey) -> usize {
+        let now = Instant::now();
+        // forget peers silent for a long time
+        self.quotas.retain(|_, q| now.duration_since(q.window_start) < RATE_WINDOW * 60);
+        let quota = self.quotas.entry(*peer).or_insert(Quota {
+            window_start: now,
+            served: 0
+        });
+        if now.duration_since(quota.window_start) >= RATE_WINDOW {
+            quota.window_start = now;
+            quota.served = 0;
+        }
+        MAX_BLOCKS_PER_WINDOW - quota.served
+    }
+}
+
+#[test]
+fn test_block_server() {
+    use super::super::Hash;
+    use super::super::csdb::test_pool;
+    use super::pool_sync::parse_blocks;
+
+    let (mut storage, _dir) = super::super::storage::test_storage("block-server");
+    let mut prev = Hash::default();
+    for seq in 0..3 {
+        prev = storage.store(&test_pool(prev, seq)).unwrap();
+    }
+    let peer = [1u8; 32];
+    let mut server = BlockServer::new();
+
+    let replies = server.replies(&storage, &peer, &[1, 2, 7], 5, false);
+    assert_eq!(replies.len(), 1);
+    let (blocks, packet) = parse_blocks(&replies[0]).unwrap();
+    assert_eq!((blocks.len(), packet), (2, 5));
+    assert_eq!(blocks[1], &storage.get_bytes(2).unwrap().unwrap()[..]);
+
+    assert_eq!(server.replies(&storage, &peer, &[0, 1, 2], 6, true).len(), 3);
+
+    // 5 blocks are served in this window already, missing ones are not counted
+    let mut many: Vec<u64> = vec![10, 11];
+    many.extend((0..MAX_BLOCKS_PER_WINDOW as u64).map(|i| i % 3));
+    let replies = server.replies(&storage, &peer, &many, 7, true);
+    assert_eq!(replies.len(), MAX_BLOCKS_PER_WINDOW - 5);
+    assert!(server.replies(&storage, &peer, &[0], 8, true).is_empty());
+    assert_eq!(server.replies(&storage, &[2u8; 32], &[0], 8, true).len(), 1);
+}
@@ -0,0 +1,130 @@
+use log::{debug, info, warn};
+
+use super::node_state::SyncState;
+
+mod pool_sync;
+pub use pool_sync::{PoolSync, pack_block_request, parse_block_request, parse_blocks};
+mod pending_blocks;
+pub use pending_blocks::PendingBlocks;
+mod block_server;
+pub use block_server::BlockServer;
+
+/// max allowed lag of our stored sequence behind the neighbours' one to be still synced
+const MAX_SEQUENCE_LAG: u64 = 1;
+/// max allowed lag of our current round behind the neighbours' one to be still synced
+const MAX_ROUND_LAG: u64 = 2;
+/// round count without stored sequence progress to consider synchronization stalled
+const STALLED_ROUNDS: u64 = 50;
+
+/// Compares our stored sequence and current round against the neighbours' maxima
+/// and tracks synchronization progress
+pub struct SyncStateMachine {
+    state: SyncState,
+    /// stored sequence the last time progress was seen
+    progress_sequence: u64,
+    /// round the last time progress was seen
+    progress_round: u64
+}
+
+impl SyncStateMachine {
+
+    pub fn new() -> SyncStateMachine {
+        SyncStateMachine {
+            state: SyncState::Synced,
+            progress_sequence: 0,
+            progress_round: 0
+        }
+    }
+
+    pub fn state(&self) -> SyncState {
+        self.state
+    }
+
+    /// re-evaluates the state, returns the new one if it is changed
+    pub fn update(&mut self, sequence: u64, round: u64, network_sequence: u64, network_round: u64) -> Option<SyncState> {
+        let behind = network_sequence > sequence + MAX_SEQUENCE_LAG || network_round > round + MAX_ROUND_LAG;
+        let progress = sequence > self.progress_sequence;
+        if progress {
+            self.progress_sequence = sequence;
+            self.progress_round = round;
+        }
+
+        let next = match self.state {
+            SyncState::Synced => {
+                if behind {
+                    info!("we are behind the network: S {} / {}, R {} / {}", sequence, network_sequence, round, network_round);
+                    SyncState::Behind
+                }
+                else {
+                    SyncState::Synced
+                }
+            }
+            SyncState::Behind => {
+                if behind {
+                    SyncState::Behind
+                }
+                else {
+                    SyncState::Synced
+                }
+            }
+            SyncState::Syncing => {
+                if !behind {
+                    SyncState::Synced
+                }
+                else if !progress && round >= self.progress_round + STALLED_ROUNDS {
+                    warn!("no sync progress during {} rounds, stored sequence {}", round - self.progress_round, sequence);
+                    SyncState::Stalled
+                }
+                else {
+                    SyncState::Syncing
+                }
+            }
+            SyncState::Stalled => {
+                if !behind {
+                    SyncState::Synced
+                }
+                else if progress {
+                    SyncState::Syncing
+                }
+                else {
+                    SyncState::Stalled
+                }
+            }
+        };
+        self.set(next, round)
+    }
+
+    /// block synchronization is started
+    pub fn start_syncing(&mut self, round: u64) -> Option<SyncState> {
+        if self.state != SyncState::Behind {
+            return None;
+        }
+        self.set(SyncState::Syncing, round)
+    }
+
+    fn set(&mut self, next: SyncState, round: u64) -> Option<SyncState> {
+        if next == self.state {
+            return None;
+        }
+        debug!("sync state: {} -> {}", self.state, next);
+        if next == SyncState::Syncing && self.state == SyncState::Behind {
+            // start counting stall rounds from now
+            self.progress_round = round;
+        }
+        self.state = next;
+        Some(next)
+    }
+}
+
+#[test]
+fn test_sync_transitions() {
+    let mut sm = SyncStateMachine::new();
+    assert_eq!(sm.update(10, 20, 11, 21), None);
+    assert_eq!(sm.update(10, 20, 15, 21), Some(SyncState::Behind));
+    assert_eq!(sm.start_syncing(20), Some(SyncState::Syncing));
+    assert_eq!(sm.update(12, 21, 15, 21), None);
+    assert_eq!(sm.update(12, 21 + STALLED_ROUNDS, 15, 21 + STALLED_ROUNDS), Some(SyncState::Stalled));
+    assert_eq!(sm.update(13, 22 + STALLED_ROUNDS, 15, 22 + STALLED_ROUNDS), Some(SyncState::Syncing));
+    assert_eq!(sm.update(15, 23 + STALLED_ROUNDS, 15, 23 + STALLED_ROUNDS), Some(SyncState::Synced));
+    assert_eq!(sm.state(), SyncState::Synced);
+}
@@ -0,0 +1,116 @@
+use std::collections::BTreeMap;
+
+use log::debug;
+
+use super::super::Hash;
+use super::super::csdb::Pool;
+
+/// max count of blocks ahead of storage to keep
+const MAX_PENDING_BLOCKS: usize = 1000;
+
+/// Blocks received ahead of the next required sequence, applied to storage in order as gaps fill
+pub struct PendingBlocks {
+    blocks: BTreeMap<u64, (Pool, Hash)>
+}
+
+impl PendingBlocks {
+
+    pub fn new() -> PendingBlocks {
+        PendingBlocks {
+            blocks: BTreeMap::new()
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// caches block, returns false if it is dropped as duplicate, conflicting or exceeding the cache
+    pub fn insert(&mut self, pool: Pool, hash: Hash) -> bool {
+        let seq = pool.sequence;
+        if let Some((_, cached)) = self.blocks.get(&seq) {
+            if *cached == hash {
+                return false;
+            }
+            // the cached block of the next sequence decides which one is in chain
+            let confirmed = seq.checked_add(1).and_then(|s| self.blocks.get(&s)).map(|(next, _)| next.previous_hash == hash).unwrap_or(false);
+            if !confirmed {
+                debug!("block {} conflicts with cached {}, drop it", hash, cached);
+                return false;
+            }
+            debug!("cached block {} is replaced by {} confirmed by the next one", cached, hash);
+        }
+        else if self.blocks.len() >= MAX_PENDING_BLOCKS {
+            let farthest = *self.blocks.keys().next_back().unwrap();
+            if seq > farthest {
+                return false;
+            }
+            self.blocks.remove(&farthest);
+        }
+        if let Some(prev_seq) = seq.checked_sub(1) {
+            if let Some((_, prev_hash)) = self.blocks.get(&prev_seq) {
+                if pool.previous_hash != *prev_hash {
+                    debug!("block {} does not follow cached {}, drop the cached one", seq, prev_seq);
+                    self.blocks.remove(&prev_seq);
+                }
+            }
+        }
+        self.blocks.insert(seq, (pool, hash));
+        true
+    }
+
+    /// takes the block of sequence if any
+    pub fn take(&mut self, sequence: u64) -> Option<(Pool, Hash)> {
+        self.blocks.remove(&sequence)
+    }
+
+    /// drops blocks up to sequence which are stored already
+    pub fn drop_stored(&mut self, sequence: u64) {
+        self.blocks = self.blocks.split_off(&(sequence + 1));
+    }
+
+    /// ranges of sequences from up to target which are not cached
+    pub fn missing_ranges(&self, from: u64, target: u64) -> Vec<(u64, u64)> {
+        let mut ranges = Vec::<(u64, u64)>::new();
+        let mut start = from;
+        for seq in self.blocks.range(from..=target).map(|(s, _)| *s) {
+            if seq > start {
+                ranges.push((start, seq - 1));
+            }
+            start = seq + 1;
+        }
+        if start <= target {
+            ranges.push((start, target));
+        }
+        ranges
+    }
+}
+
+#[test]
+fn test_pending_blocks() {
+    use super::super::csdb::test_pool;
+
+    let p5 = test_pool(Hash([5u8; 32]), 5);
+    let h5 = p5.hash();
+    let p6 = test_pool(h5, 6);
+    let h6 = p6.hash();
+    let mut pending = PendingBlocks::new();
+    assert!(pending.insert(p6.clone(), h6));
+    assert!(!pending.insert(p6.clone(), h6));
+    assert_eq!(pending.missing_ranges(3, 10), vec![(3, 5), (7, 10)]);
+
+    // another block 5 is dropped, the one the block 6 refers replaces it
+    let other5 = test_pool(Hash([6u8; 32]), 5);
+    assert!(pending.insert(other5.clone(), other5.hash()));
+    assert!(pending.insert(p5.clone(), h5));
+    assert!(!pending.insert(other5.clone(), other5.hash()));
+    assert_eq!(pending.missing_ranges(5, 6), vec![]);
+
+    assert_eq!(pending.take(5).unwrap().1, h5);
+    pending.drop_stored(6);
+    assert_eq!(pending.len(), 0);
+
+    // the lowest sequence has no previous one
+    let p0 = test_pool(Hash::default(), 0);
+    assert!(pending.insert(p0.clone(), p0.hash()));
+}
@@ -0,0 +1,320 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::convert::TryInto;
+use std::time::Instant;
+
+use log::debug;
+
+use super::super::PublicKey;
+use super::super::config::SyncConfig;
+
+extern crate base58;
+use base58::ToBase58;
+
+/// BlockRequest to send
+pub struct BlockRequest {
+    pub target: PublicKey,
+    pub sequences: Vec<u64>,
+    /// number of request packet, the reply refers it
+    pub packet: u64
+}
+
+/// Requests missing blocks from the best neighbours
+pub struct PoolSync {
+    /// requested sequences: sequence -> (neighbour, round the request is sent)
+    requested: BTreeMap<u64, (PublicKey, u64)>,
+    /// neighbour to request blocks from
+    neighbour: Option<PublicKey>,
+    /// count of packets sent to the neighbour
+    neighbour_packets: u8,
+    packet_counter: u64,
+    last_update: Option<Instant>
+}
+
+impl PoolSync {
+
+    pub fn new() -> PoolSync {
+        PoolSync {
+            requested: BTreeMap::new(),
+            neighbour: None,
+            neighbour_packets: 0,
+            packet_counter: 0,
+            last_update: None
+        }
+    }
+
+    /// required blocks are updated now
+    pub fn on_update(&mut self) {
+        self.last_update = Some(Instant::now());
+    }
+
+    /// test it is time to update required blocks by timer, delay > 1 is in ms
+    pub fn is_update_time(&self, delay: u16) -> bool {
+        if delay <= 1 {
+            return false;
+        }
+        match self.last_update {
+            None => true,
+            Some(t) => t.elapsed().as_millis() as u64 >= delay as u64
+        }
+    }
+
+    /// ranges of sequences from up to target which are not requested or requested
+    /// at least request_round_delay rounds before rnd
+    pub fn required_ranges(&self, conf: &SyncConfig, from: u64, target: u64, rnd: u64) -> Vec<(u64, u64)> {
+        let mut ranges = Vec::<(u64, u64)>::new();
+        let mut start = from;
+        for (seq, (_, sent)) in self.requested.range(from..=target) {
+            if conf.request_round_delay != 0 && rnd >= sent + conf.request_round_delay as u64 {
+                // the request is outdated, the block is required again
+                continue;
+            }
+            if *seq > start {
+                ranges.push((start, seq - 1));
+            }
+            start = seq + 1;
+        }
+        if start <= target {
+            ranges.push((start, target));
+        }
+        ranges
+    }
+
+    /// max count of sequences to scan for required blocks per update
+    pub fn scan_limit(conf: &SyncConfig, neighbours: usize) -> u64 {
+        std::cmp::max(1, conf.max_block_request) as u64 * std::cmp::max(1, neighbours) as u64
+    }
+
+    /// builds requests of the required sequences from ranges, batched by max_block_request;
+    /// neighbours are ordered from the best one
+    pub fn next_requests(&mut self, conf: &SyncConfig, ranges: &[(u64, u64)], rnd: u64, neighbours: &[(PublicKey, u64)]) -> Vec<BlockRequest> {
+        let mut requests = Vec::<BlockRequest>::new();
+        if neighbours.is_empty() {
+            return requests;
+        }
+        let batch = std::cmp::max(1, conf.max_block_request as usize);
+        let max_requests = std::cmp::min(std::cmp::max(1, conf.parallel_requests as usize), neighbours.len());
+        let mut ranges: VecDeque<(u64, u64)> = ranges.iter().cloned().collect();
+        while requests.len() < max_requests {
+            let first = match ranges.front() {
+                None => break,
+                Some(r) => r.0
+            };
+            let (target, available) = match self.select_neighbour(conf, neighbours, first) {
+                None => {
+                    debug!("no neighbour has block {} yet", first);
+                    break;
+                }
+                Some(v) => v
+            };
+            let mut items = Vec::<u64>::with_capacity(batch);
+            while items.len() < batch {
+                let (a, b) = match ranges.front() {
+                    Some(r) if r.0 <= available => *r,
+                    _ => break
+                };
+                let end = std::cmp::min(std::cmp::min(b, available), a + (batch - items.len()) as u64 - 1);
+                items.extend(a..=end);
+                if end == b {
+                    ranges.pop_front();
+                }
+                else {
+                    ranges[0].0 = end + 1;
+                }
+            }
+            for seq in &items {
+                self.requested.insert(*seq, (target, rnd));
+            }
+            self.packet_counter += 1;
+            debug!("request {} blocks {}..{} from {}", items.len(), items[0], items[items.len() - 1], target.to_base58());
+            requests.push(BlockRequest {
+                target: target,
+                sequences: items,
+                packet: self.packet_counter
+            });
+        }
+        requests
+    }
+
+    pub fn is_requested(&self, sequence: u64) -> bool {
+        self.requested.contains_key(&sequence)
+    }
+
+    /// the block is received, it is not requested anymore
+    pub fn on_received(&mut self, sequence: u64) {
+        self.requested.remove(&sequence);
+    }
+
+    /// blocks up to sequence are stored
+    pub fn on_stored(&mut self, sequence: u64) {
+        self.requested = self.requested.split_off(&(sequence + 1));
+    }
+
+    /// current neighbour while it is in list and is not used max_neighbour_req_count times,
+    /// then the next one having the required block; returns neighbour and its sequence
+    fn select_neighbour(&mut self, conf: &SyncConfig, neighbours: &[(PublicKey, u64)], required: u64) -> Option<(PublicKey, u64)> {
+        let pos = self.neighbour.and_then(|n| neighbours.iter().position(|(k, _)| *k == n));
+        let next = match pos {
+            None => 0,
+            Some(i) => {
+                if conf.max_neighbour_req_count > 0 && self.neighbour_packets >= conf.max_neighbour_req_count {
+                    debug!("switch to the next neighbour after {} packets", self.neighbour_packets);
+                    (i + 1) % neighbours.len()
+                }
+                else {
+                    i
+                }
+            }
+        };
+        let next = (0..neighbours.len())
+            .map(|k| (next + k) % neighbours.len())
+            .find(|i| neighbours[*i].1 >= required)?;
+        if pos != Some(next) {
+            self.neighbour_packets = 0;
+        }
+        self.neighbour = Some(neighbours[next].0);
+        self.neighbour_packets = self.neighbour_packets.saturating_add(1);
+        Some(neighbours[next])
+    }
+}
+
+/// encodes payload of BlockRequest, the layout parse_block_request() reads
+pub fn pack_block_request(sequences: &[u64], packet: u64) -> Vec<u8> {
+    let mut output = Vec::<u8>::with_capacity(8 + sequences.len() * 8 + 8);
+    output.extend_from_slice(&(sequences.len() as u64).to_le_bytes());
+    for seq in sequences {
+        output.extend_from_slice(&seq.to_le_bytes());
+    }
+    output.extend_from_slice(&packet.to_le_bytes());
+    output
+}
+
+/// decodes payload of BlockRequest: requested sequences and packet number
+pub fn parse_block_request(input: &[u8]) -> Option<(Vec<u64>, u64)> {
+    /*
+        cs::PoolsRequestedSequences sequences;
+        stream >> sequences;
+        std::size_t packetNum = 0;
+        stream >> packetNum;
+
+        std::vector is streamed as size(8) + items
+    */
+    if input.len() < 8 + 8 {
+        return None;
+    }
+    let count = u64::from_le_bytes(input[..8].try_into().unwrap());
+    let rest = input.len() - 8 - 8;
+    if rest % 8 != 0 || (rest / 8) as u64 != count {
+        return None;
+    }
+    let sequences = input[8..8 + rest].chunks(8).map(|c| u64::from_le_bytes(c.try_into().unwrap())).collect();
+    let packet = u64::from_le_bytes(input[8 + rest..].try_into().unwrap());
+    Some((sequences, packet))
+}
+
+/// RequestedBlock payload: blocks count(4) + blocks: size(4) + pool + packet number(8)
+pub fn pack_blocks(blocks: &[Vec<u8>], packet: u64) -> Vec<u8> {
+    let mut output = (blocks.len() as u32).to_le_bytes().to_vec();
+    for b in blocks {
+        output.extend_from_slice(&(b.len() as u32).to_le_bytes());
+        output.extend_from_slice(b);
+    }
+    output.extend_from_slice(&packet.to_le_bytes());
+    output
+}
+
+pub fn parse_blocks(input: &[u8]) -> Option<(Vec<&[u8]>, u64)> {
+    if input.len() < 4 + 8 {
+        return None;
+    }
+    let count = u32::from_le_bytes(input[..4].try_into().unwrap());
+    let mut rest = &input[4..input.len() - 8];
+    let mut blocks = Vec::<&[u8]>::new();
+    for _ in 0..count {
+        if rest.len() < 4 {
+            return None;
+        }
+        let size = u32::from_le_bytes(rest[..4].try_into().unwrap()) as usize;
+        if rest.len() < 4 + size {
+            return None;
+        }
+        blocks.push(&rest[4..4 + size]);
+        rest = &rest[4 + size..];
+    }
+    if !rest.is_empty() {
+        return None;
+    }
+    let packet = u64::from_le_bytes(input[input.len() - 8..].try_into().unwrap());
+    Some((blocks, packet))
+}
+
+#[test]
+fn test_pool_sync_requests() {
+    let mut conf = SyncConfig::new();
+    conf.max_block_request = 3;
+    conf.request_round_delay = 2;
+    conf.max_neighbour_req_count = 2;
+    let a = [1u8; 32];
+    let b = [2u8; 32];
+    let neighbours = vec![(a, 20), (b, 5)];
+
+    let mut ps = PoolSync::new();
+    assert!(ps.is_update_time(350));
+    ps.on_update();
+    assert!(!ps.is_update_time(350));
+    let ranges = ps.required_ranges(&conf, 1, 10, 100);
+    assert_eq!(ranges, vec![(1, 10)]);
+    let requests = ps.next_requests(&conf, &ranges, 100, &neighbours);
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].sequences, vec![1, 2, 3]);
+    assert!(requests[0].target == a);
+
+    assert!(ps.is_requested(2) && !ps.is_requested(4));
+    ps.on_received(2);
+    assert_eq!(ps.required_ranges(&conf, 1, 10, 101), vec![(2, 2), (4, 10)]);
+    // requests are repeated after request_round_delay rounds
+    assert_eq!(ps.required_ranges(&conf, 1, 10, 102), vec![(1, 10)]);
+    ps.on_stored(3);
+    assert_eq!(ps.requested.len(), 0);
+
+    // the second packet to a, then switch to b which has blocks up to 5 only
+    assert!(ps.next_requests(&conf, &[(4, 10)], 102, &neighbours)[0].target == a);
+    let requests = ps.next_requests(&conf, &[(4, 10)], 102, &neighbours);
+    assert!(requests[0].target == b);
+    assert_eq!(requests[0].sequences, vec![4, 5]);
+    // b has no block 6, both parallel requests go to a
+    conf.parallel_requests = 2;
+    let requests = ps.next_requests(&conf, &[(6, 10)], 103, &neighbours);
+    assert_eq!(requests.len(), 2);
+    assert!(requests[0].target == a && requests[1].target == a);
+    assert_eq!(requests[1].sequences, vec![9, 10]);
+    assert_eq!(requests[1].packet, 5);
+    assert_eq!(ps.next_requests(&conf, &[(21, 21)], 103, &neighbours).len(), 0);
+
+    // huge lag is not expanded into single sequences
+    let far = vec![(a, u64::MAX / 2)];
+    let requests = ps.next_requests(&conf, &[(30, 31), (40, u64::MAX / 4)], 104, &far);
+    assert_eq!(requests[0].sequences, vec![30, 31, 40]);
+    assert_eq!(ps.required_ranges(&conf, 30, u64::MAX / 4, 104), vec![(32, 39), (41, u64::MAX / 4)]);
+    assert_eq!(PoolSync::scan_limit(&conf, 2), 6);
+}
+
+#[test]
+fn test_block_messages() {
+    let bytes = pack_block_request(&[5, 6], 9);
+    assert_eq!(bytes, vec![
+        2u8, 0, 0, 0, 0, 0, 0, 0,
+        5, 0, 0, 0, 0, 0, 0, 0,
+        6, 0, 0, 0, 0, 0, 0, 0,
+        9, 0, 0, 0, 0, 0, 0, 0
+    ]);
+    assert_eq!(parse_block_request(&bytes), Some((vec![5, 6], 9)));
+    assert_eq!(parse_block_request(&bytes[1..]), None);
+    assert_eq!(parse_block_request(&pack_block_request(&[], 1)), Some((vec![], 1)));
+
+    let blocks = vec![vec![1u8, 2u8], vec![3u8]];
+    let bytes = pack_blocks(&blocks, 7);
+    let (parsed, packet) = parse_blocks(&bytes).unwrap();
+    assert_eq!(parsed, vec![&[1u8, 2u8][..], &[3u8][..]]);
+    assert_eq!(packet, 7);
+    assert!(parse_blocks(&bytes[..bytes.len() - 1]).is_none());
+}